@@ -7,8 +7,10 @@ use tokio::sync::RwLock;
 use crate::SdkResult;
 
 pub struct BlockhashSubscriber {
-    latest_blockhash: Hash,
-    last_twenty_hashes: Vec<Hash>,
+    /// latest blockhash and its last valid block height
+    latest_blockhash: (Hash, u64),
+    /// recent blockhashes and their last valid block heights, oldest first
+    last_twenty_hashes: Vec<(Hash, u64)>,
     refresh_frequency: u64,
     rpc_client: RpcClient,
 }
@@ -16,7 +18,7 @@ pub struct BlockhashSubscriber {
 impl BlockhashSubscriber {
     pub fn new(refresh_frequency: u64, endpoint: String) -> Self {
         BlockhashSubscriber {
-            latest_blockhash: Hash::default(),
+            latest_blockhash: (Hash::default(), 0),
             last_twenty_hashes: Vec::with_capacity(20),
             refresh_frequency,
             rpc_client: RpcClient::new(endpoint),
@@ -32,18 +34,12 @@ impl BlockhashSubscriber {
         tokio::spawn(async move {
             loop {
                 let mut blockhash_subscriber_writer = blockhash_subscriber.write().await;
-                let blockhash = blockhash_subscriber_writer
-                    .rpc_client
-                    .get_latest_blockhash()
+                let rpc_client = &blockhash_subscriber_writer.rpc_client;
+                let blockhash = rpc_client
+                    .get_latest_blockhash_with_commitment(rpc_client.commitment())
                     .await
                     .expect("blockhash");
-                blockhash_subscriber_writer
-                    .last_twenty_hashes
-                    .push(blockhash);
-                blockhash_subscriber_writer.latest_blockhash = blockhash;
-                if blockhash_subscriber_writer.last_twenty_hashes.len() > 20 {
-                    blockhash_subscriber_writer.last_twenty_hashes.remove(0);
-                }
+                blockhash_subscriber_writer.push_blockhash(blockhash);
                 drop(blockhash_subscriber_writer);
                 tokio::time::sleep(tokio::time::Duration::from_secs(refresh_frequency)).await;
             }
@@ -52,11 +48,30 @@ impl BlockhashSubscriber {
         Ok(())
     }
 
+    /// Record a new blockhash and its last valid block height
+    pub(crate) fn push_blockhash(&mut self, blockhash: (Hash, u64)) {
+        self.last_twenty_hashes.push(blockhash);
+        self.latest_blockhash = blockhash;
+        if self.last_twenty_hashes.len() > 20 {
+            self.last_twenty_hashes.remove(0);
+        }
+    }
+
     pub fn get_latest_blockhash(&self) -> Hash {
-        self.latest_blockhash
+        self.latest_blockhash.0
     }
 
     pub fn get_valid_blockhash(&self) -> Hash {
+        self.get_valid_blockhash_with_height().0
+    }
+
+    /// Returns the latest blockhash and the last block height at which it is valid
+    pub fn get_latest_blockhash_with_height(&self) -> (Hash, u64) {
+        self.latest_blockhash
+    }
+
+    /// Returns a valid blockhash and the last block height at which it is valid
+    pub fn get_valid_blockhash_with_height(&self) -> (Hash, u64) {
         *self
             .last_twenty_hashes
             .first()
@@ -0,0 +1,416 @@
+//! OHLCV candles, VWAP and trade tape built from drift fill events
+//!
+//! ```ignore
+//! let mut candles = CandleAggregator::new(&[60, 3_600], 1_440, 500);
+//! candles.add_market(MarketId::perp(0), &sol_perp_market);
+//! candles.consume(EventSubscriber::subscribe_program(endpoint, filter, retry_policy).await?).await;
+//! ```
+use std::collections::VecDeque;
+
+use futures_util::{Stream, StreamExt};
+
+use crate::{
+    event_subscriber::DriftEvent,
+    types::{MarketId, MarketPrecision, PositionDirection},
+};
+
+/// A trade parsed from an `OrderFill` event
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trade {
+    pub market: MarketId,
+    /// average fill price in `PRICE_PRECISION`, rounded to the market price tick
+    pub price: u64,
+    /// filled base amount in market base precision
+    pub base_amount: u64,
+    /// filled quote amount in `QUOTE_PRECISION`
+    pub quote_amount: u64,
+    pub taker_side: Option<PositionDirection>,
+    /// unix timestamp (s) of the fill
+    pub ts: u64,
+    pub signature: String,
+}
+
+/// OHLCV candle of a market
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Candle {
+    /// unix timestamp (s) of the candle start
+    pub start_ts: u64,
+    /// length of the candle in seconds
+    pub resolution: u64,
+    pub open: u64,
+    pub high: u64,
+    pub low: u64,
+    pub close: u64,
+    /// traded base volume in market base precision
+    pub base_volume: u64,
+    /// traded quote volume in `QUOTE_PRECISION`
+    pub quote_volume: u64,
+    /// volume weighted average price in `PRICE_PRECISION`
+    pub vwap: u64,
+    /// number of trades
+    pub trades: u32,
+    /// unix timestamp (s) of the earliest trade i.e. the `open`
+    pub open_ts: u64,
+    /// unix timestamp (s) of the latest trade i.e. the `close`
+    pub close_ts: u64,
+}
+
+impl Candle {
+    fn new(start_ts: u64, resolution: u64, trade: &Trade, base_precision: u64) -> Self {
+        let mut candle = Self {
+            start_ts,
+            resolution,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            open_ts: trade.ts,
+            close_ts: trade.ts,
+            ..Default::default()
+        };
+        candle.update(trade, base_precision);
+        candle
+    }
+    fn update(&mut self, trade: &Trade, base_precision: u64) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        // fills may arrive out of order, open/close follow the fill timestamps
+        if trade.ts < self.open_ts {
+            self.open = trade.price;
+            self.open_ts = trade.ts;
+        }
+        if trade.ts >= self.close_ts {
+            self.close = trade.price;
+            self.close_ts = trade.ts;
+        }
+        self.base_volume += trade.base_amount;
+        self.quote_volume += trade.quote_amount;
+        self.vwap = vwap(self.quote_volume, self.base_volume, base_precision);
+        self.trades += 1;
+    }
+}
+
+/// Volume weighted average price in `PRICE_PRECISION`
+fn vwap(quote_volume: u64, base_volume: u64, base_precision: u64) -> u64 {
+    if base_volume == 0 {
+        return 0;
+    }
+    (quote_volume as u128 * base_precision as u128 / base_volume as u128) as u64
+}
+
+/// Candles and trades of a single market
+struct MarketCandles {
+    market: MarketId,
+    base_precision: u64,
+    price_tick: u64,
+    /// candles per resolution, oldest to newest
+    candles: Vec<VecDeque<Candle>>,
+    /// most recent trades, oldest to newest
+    tape: VecDeque<Trade>,
+    base_volume: u64,
+    quote_volume: u64,
+}
+
+/// Maintains per-market OHLCV candles, VWAP and a rolling trade tape from `OrderFill` events
+///
+/// Candles are bucketed by the on-chain fill timestamp, periods without trades produce no candle
+pub struct CandleAggregator {
+    /// candle resolutions in seconds
+    resolutions: Vec<u64>,
+    /// max. candles kept per market and resolution
+    max_candles: usize,
+    /// max. trades kept per market
+    tape_size: usize,
+    markets: Vec<MarketCandles>,
+}
+
+impl CandleAggregator {
+    /// Create a new aggregator
+    ///
+    /// `resolutions` candle lengths in seconds e.g. `[60, 3_600]`
+    /// `max_candles` number of candles retained per market and resolution
+    /// `tape_size` number of trades retained per market
+    pub fn new(resolutions: &[u64], max_candles: usize, tape_size: usize) -> Self {
+        Self {
+            resolutions: resolutions.iter().copied().filter(|r| *r > 0).collect(),
+            max_candles,
+            tape_size,
+            markets: Vec::default(),
+        }
+    }
+    /// Track fills of `market`, fills of untracked markets are ignored
+    ///
+    /// `precision` the market account e.g. `PerpMarket` or `SpotMarket`
+    pub fn add_market(&mut self, market: MarketId, precision: &impl MarketPrecision) {
+        if self.market(market).is_some() {
+            return;
+        }
+        self.markets.push(MarketCandles {
+            market,
+            base_precision: precision.base_precision().max(1),
+            price_tick: precision.price_tick().max(1),
+            candles: vec![VecDeque::default(); self.resolutions.len()],
+            tape: VecDeque::with_capacity(self.tape_size),
+            base_volume: 0,
+            quote_volume: 0,
+        });
+    }
+    /// Update with `event`, returning the trade if it was a fill of a tracked market
+    pub fn on_event(&mut self, event: &DriftEvent) -> Option<Trade> {
+        let DriftEvent::OrderFill {
+            base_asset_amount_filled,
+            quote_asset_amount_filled,
+            market_index,
+            market_type,
+            taker_side,
+            ts,
+            signature,
+            ..
+        } = event
+        else {
+            return None;
+        };
+        if *base_asset_amount_filled == 0 {
+            return None;
+        }
+        let market = MarketId::from((*market_index, *market_type));
+        let (resolutions, max_candles, tape_size) =
+            (&self.resolutions, self.max_candles, self.tape_size);
+        let state = self.markets.iter_mut().find(|m| m.market == market)?;
+
+        let price = vwap(
+            *quote_asset_amount_filled,
+            *base_asset_amount_filled,
+            state.base_precision,
+        );
+        let tick = state.price_tick;
+        let trade = Trade {
+            market,
+            price: (price + tick / 2) / tick * tick,
+            base_amount: *base_asset_amount_filled,
+            quote_amount: *quote_asset_amount_filled,
+            taker_side: *taker_side,
+            ts: *ts,
+            signature: signature.clone(),
+        };
+
+        for (resolution, candles) in resolutions.iter().zip(state.candles.iter_mut()) {
+            let start_ts = trade.ts - trade.ts % resolution;
+            // candles are sorted by start time, late fills update or fill in their candle
+            match candles.binary_search_by_key(&start_ts, |c| c.start_ts) {
+                Ok(idx) => candles[idx].update(&trade, state.base_precision),
+                // late fill older than the retained candles
+                Err(0) if candles.len() >= max_candles => (),
+                Err(idx) => {
+                    candles.insert(
+                        idx,
+                        Candle::new(start_ts, *resolution, &trade, state.base_precision),
+                    );
+                    if candles.len() > max_candles {
+                        candles.pop_front();
+                    }
+                }
+            }
+        }
+
+        state.base_volume += trade.base_amount;
+        state.quote_volume += trade.quote_amount;
+        // late fills are inserted in timestamp order
+        let idx = state.tape.partition_point(|t| t.ts <= trade.ts);
+        state.tape.insert(idx, trade.clone());
+        if state.tape.len() > tape_size {
+            state.tape.pop_front();
+        }
+
+        Some(trade)
+    }
+    /// Update with all events of `events` until the stream ends
+    pub async fn consume(&mut self, mut events: impl Stream<Item = DriftEvent> + Unpin) {
+        while let Some(event) = events.next().await {
+            self.on_event(&event);
+        }
+    }
+    /// Candles of `market` at `resolution`, oldest to newest
+    pub fn candles(&self, market: MarketId, resolution: u64) -> impl Iterator<Item = &Candle> {
+        self.market(market)
+            .zip(self.resolutions.iter().position(|r| *r == resolution))
+            .into_iter()
+            .flat_map(|(state, idx)| state.candles[idx].iter())
+    }
+    /// The most recent candle of `market` at `resolution`
+    pub fn latest_candle(&self, market: MarketId, resolution: u64) -> Option<&Candle> {
+        self.candles(market, resolution).last()
+    }
+    /// Volume weighted average price of `market` over all fills seen, in `PRICE_PRECISION`
+    pub fn vwap(&self, market: MarketId) -> Option<u64> {
+        self.market(market)
+            .filter(|m| m.base_volume > 0)
+            .map(|m| vwap(m.quote_volume, m.base_volume, m.base_precision))
+    }
+    /// Most recent trades of `market`, oldest to newest
+    pub fn trades(&self, market: MarketId) -> impl Iterator<Item = &Trade> {
+        self.market(market)
+            .into_iter()
+            .flat_map(|state| state.tape.iter())
+    }
+    fn market(&self, market: MarketId) -> Option<&MarketCandles> {
+        self.markets.iter().find(|m| m.market == market)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use drift::{
+        math::constants::{BASE_PRECISION_U64, PRICE_PRECISION_U64, QUOTE_PRECISION_U64},
+        state::{perp_market::PerpMarket, user::MarketType},
+    };
+
+    use super::*;
+
+    fn fill(market_index: u16, price: u64, base_amount: u64, ts: u64) -> DriftEvent {
+        DriftEvent::OrderFill {
+            maker: None,
+            maker_fee: 0,
+            maker_order_id: 0,
+            maker_side: None,
+            taker: None,
+            taker_fee: 0,
+            taker_order_id: 0,
+            taker_side: Some(PositionDirection::Long),
+            base_asset_amount_filled: base_amount,
+            quote_asset_amount_filled: (base_amount as u128 * price as u128
+                / BASE_PRECISION_U64 as u128) as u64,
+            market_index,
+            market_type: MarketType::Perp,
+            oracle_price: price as i64,
+            signature: "sig".into(),
+            tx_idx: 0,
+            ts,
+        }
+    }
+
+    #[test]
+    fn candles_vwap_and_tape() {
+        let mut perp = PerpMarket::default();
+        perp.amm.order_tick_size = 100;
+        let market = MarketId::perp(0);
+
+        let mut aggregator = CandleAggregator::new(&[60, 300], 2, 3);
+        aggregator.add_market(market, &perp);
+
+        let price = 100 * PRICE_PRECISION_U64;
+        let events = [
+            fill(0, price, BASE_PRECISION_U64, 600),
+            fill(0, price + 2 * PRICE_PRECISION_U64, BASE_PRECISION_U64, 610),
+            fill(0, price - PRICE_PRECISION_U64, 2 * BASE_PRECISION_U64, 659),
+            fill(0, price + PRICE_PRECISION_U64, BASE_PRECISION_U64, 660),
+            // untracked market
+            fill(1, price, BASE_PRECISION_U64, 660),
+        ];
+        for event in events.iter() {
+            aggregator.on_event(event);
+        }
+
+        let minute: Vec<&Candle> = aggregator.candles(market, 60).collect();
+        assert_eq!(minute.len(), 2);
+        assert_eq!(
+            *minute[0],
+            Candle {
+                start_ts: 600,
+                resolution: 60,
+                open: price,
+                high: price + 2 * PRICE_PRECISION_U64,
+                low: price - PRICE_PRECISION_U64,
+                close: price - PRICE_PRECISION_U64,
+                base_volume: 4 * BASE_PRECISION_U64,
+                quote_volume: 400 * QUOTE_PRECISION_U64,
+                vwap: price,
+                trades: 3,
+                open_ts: 600,
+                close_ts: 659,
+            }
+        );
+        assert_eq!(minute[1].start_ts, 660);
+        assert_eq!(minute[1].close, price + PRICE_PRECISION_U64);
+
+        let five_minute = aggregator.latest_candle(market, 300).unwrap();
+        assert_eq!(five_minute.start_ts, 600);
+        assert_eq!(five_minute.trades, 4);
+        assert_eq!(five_minute.base_volume, 5 * BASE_PRECISION_U64);
+
+        assert_eq!(aggregator.vwap(market), Some(100_200_000));
+        assert!(aggregator.vwap(MarketId::perp(1)).is_none());
+
+        // tape keeps the 3 most recent trades
+        let tape: Vec<u64> = aggregator.trades(market).map(|t| t.ts).collect();
+        assert_eq!(tape, vec![610, 659, 660]);
+
+        // max candles retained and fills older than the retained candles ignored
+        aggregator.on_event(&fill(0, price, BASE_PRECISION_U64, 720));
+        assert_eq!(
+            aggregator
+                .candles(market, 60)
+                .map(|c| c.start_ts)
+                .collect::<Vec<_>>(),
+            vec![660, 720]
+        );
+        assert!(aggregator
+            .on_event(&fill(0, price, BASE_PRECISION_U64, 600))
+            .is_some());
+        assert_eq!(aggregator.latest_candle(market, 60).unwrap().trades, 1);
+
+        // late fills between retained candles fill in their candle
+        aggregator.on_event(&fill(0, price, BASE_PRECISION_U64, 840));
+        aggregator.on_event(&fill(0, price, BASE_PRECISION_U64, 790));
+        let minute: Vec<&Candle> = aggregator.candles(market, 60).collect();
+        assert_eq!(
+            minute.iter().map(|c| c.start_ts).collect::<Vec<_>>(),
+            vec![780, 840]
+        );
+        assert_eq!(minute[0].trades, 1);
+    }
+
+    #[test]
+    fn out_of_order_fills() {
+        let market = MarketId::perp(0);
+        let mut aggregator = CandleAggregator::new(&[60], 10, 10);
+        aggregator.add_market(market, &PerpMarket::default());
+
+        let price = 100 * PRICE_PRECISION_U64;
+        for (price, ts) in [
+            (price, 70),
+            (price + 1, 100),
+            (price + 2, 65),
+            (price + 3, 90),
+        ] {
+            aggregator.on_event(&fill(0, price, BASE_PRECISION_U64, ts));
+        }
+
+        let candle = aggregator.latest_candle(market, 60).unwrap();
+        assert_eq!((candle.open, candle.open_ts), (price + 2, 65));
+        assert_eq!((candle.close, candle.close_ts), (price + 1, 100));
+        assert_eq!(candle.trades, 4);
+        let tape: Vec<u64> = aggregator.trades(market).map(|t| t.ts).collect();
+        assert_eq!(tape, vec![65, 70, 90, 100]);
+    }
+
+    #[test]
+    fn trade_price_rounds_to_tick() {
+        let mut perp = PerpMarket::default();
+        perp.amm.order_tick_size = 1_000;
+        let market = MarketId::perp(0);
+        let mut aggregator = CandleAggregator::new(&[60], 10, 10);
+        aggregator.add_market(market, &perp);
+
+        // 3 base for 100 quote
+        let mut event = fill(0, 0, 3 * BASE_PRECISION_U64, 0);
+        if let DriftEvent::OrderFill {
+            quote_asset_amount_filled,
+            ..
+        } = &mut event
+        {
+            *quote_asset_amount_filled = 100 * QUOTE_PRECISION_U64;
+        }
+        let trade = aggregator.on_event(&event).unwrap();
+        assert_eq!(trade.price, 33_333_000);
+    }
+}
@@ -19,6 +19,16 @@ static STATE_ACCOUNT: OnceLock<Pubkey> = OnceLock::new();
 pub const TOKEN_PROGRAM_ID: Pubkey =
     solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 
+/// wrapped SOL (wSOL) mint
+pub const WRAPPED_SOL_MINT: Pubkey =
+    solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+
+/// size of an SPL token account
+pub const TOKEN_ACCOUNT_SIZE: usize = 165;
+
+/// maximum compute unit limit of a tx
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
 /// Return the market lookup table
 pub(crate) const fn market_lookup_table(context: Context) -> Pubkey {
     match context {
@@ -67,6 +77,15 @@ pub fn derive_spot_market_vault(market_index: u16) -> Pubkey {
     account
 }
 
+/// calculate the PDA for a drift insurance fund vault given market index
+pub fn derive_insurance_fund_vault(market_index: u16) -> Pubkey {
+    let (account, _seed) = Pubkey::find_program_address(
+        &[&b"insurance_fund_vault"[..], &market_index.to_le_bytes()],
+        &PROGRAM_ID,
+    );
+    account
+}
+
 /// calculate the PDA for the drift signer
 pub fn derive_drift_signer() -> Pubkey {
     let (account, _seed) = Pubkey::find_program_address(&[&b"drift_signer"[..]], &PROGRAM_ID);
@@ -161,6 +180,13 @@ impl ProgramData {
         self.spot_markets.get(market_index as usize)
     }
 
+    /// Return the spot market config of native SOL, if it exists
+    pub fn sol_spot_market_config(&self) -> Option<&'static SpotMarket> {
+        self.spot_markets
+            .iter()
+            .find(|m| m.mint == WRAPPED_SOL_MINT)
+    }
+
     /// Return the perp market config given a market index
     pub fn perp_market_config_by_index(&self, market_index: u16) -> Option<&'static PerpMarket> {
         self.perp_markets.get(market_index as usize)
@@ -1,30 +1,58 @@
 #![allow(clippy::module_inception)]
 
-use dashmap::DashSet;
+use dashmap::{mapref::one::Ref, DashSet};
+use drift::controller::position::PositionDirection;
+use drift::math::constants::BASE_PRECISION_U64;
 use drift::state::oracle::OraclePriceData;
-use drift::state::user::{MarketType, Order, OrderStatus};
+use drift::state::perp_market::PerpMarket;
+use drift::state::spot_market::SpotMarket;
+use drift::state::state::FeeTier;
+use drift::state::user::{MarketType, Order, OrderStatus, OrderType, User};
 use rayon::prelude::*;
 use solana_sdk::pubkey::Pubkey;
 use std::any::Any;
-use std::collections::BinaryHeap;
+use std::collections::BTreeSet;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 
 use crate::dlob::dlob_node::{
-    create_node, get_order_signature, DLOBNode, DirectionalNode, Node, NodeType,
+    create_node, get_order_signature, DLOBNode, Node, NodeType, SortDirection, VAMMNode,
 };
-use crate::dlob::market::{get_node_subtype_and_type, Exchange, OpenOrders, SubType};
+use crate::dlob::market::{get_node_subtype_and_type, Exchange, Market, OpenOrders, SubType};
+use crate::dlob::order_list::MergeByPrice;
+use crate::dlob::snapshot::{DLOBSnapshot, SnapshotNode};
+use crate::dlob_client::{L2Level, L2Orderbook, L3Level, L3Orderbook};
 use crate::event_emitter::Event;
-use crate::math::order::is_resting_limit_order;
+use crate::math::amm::calculate_vamm_levels;
+use crate::math::auction::is_auction_complete;
+use crate::math::order::{has_auction_price, is_resting_limit_order};
+use crate::types::MarketId;
 use crate::usermap::UserMap;
 use crate::utils::market_type_to_string;
 
-#[derive(Clone)]
 pub struct DLOB {
     exchange: Exchange,
     _open_orders: OpenOrders,
     _initialized: bool,
-    _max_slot_for_resting_limit_orders: Arc<u64>,
+    _max_slot_for_resting_limit_orders: AtomicU64,
+    /// (max_ts, user, order id) of orders with an expiry
+    expiries: Mutex<BTreeSet<(i64, Pubkey, u32)>>,
+}
+
+impl Clone for DLOB {
+    fn clone(&self) -> Self {
+        Self {
+            exchange: self.exchange.clone(),
+            _open_orders: self._open_orders.clone(),
+            _initialized: self._initialized,
+            _max_slot_for_resting_limit_orders: AtomicU64::new(
+                self._max_slot_for_resting_limit_orders
+                    .load(Ordering::Relaxed),
+            ),
+            expiries: Mutex::new(self.expiries.lock().unwrap().clone()),
+        }
+    }
 }
 
 impl DLOB {
@@ -39,7 +67,8 @@ impl DLOB {
             exchange,
             _open_orders: open_orders,
             _initialized: true,
-            _max_slot_for_resting_limit_orders: Arc::new(0),
+            _max_slot_for_resting_limit_orders: AtomicU64::new(0),
+            expiries: Mutex::default(),
         }
     }
 
@@ -75,16 +104,35 @@ impl DLOB {
         self.exchange.clear();
         self._open_orders.clear();
         self._initialized = false;
-        self._max_slot_for_resting_limit_orders = Arc::new(0);
+        self._max_slot_for_resting_limit_orders
+            .store(0, Ordering::Relaxed);
+        self.expiries.lock().unwrap().clear();
     }
 
     pub fn insert_order(&self, order: &Order, user_account: Pubkey, slot: u64) {
+        let (subtype, node_type) = get_node_subtype_and_type(order, slot);
+        self.insert_node(subtype, node_type, order, user_account);
+    }
+
+    fn insert_node(
+        &self,
+        subtype: SubType,
+        node_type: NodeType,
+        order: &Order,
+        user_account: Pubkey,
+    ) {
         let market_type = market_type_to_string(&order.market_type);
         let market_index = order.market_index;
-
-        let (subtype, node_type) = get_node_subtype_and_type(order, slot);
         let node = create_node(node_type, *order, user_account);
 
+        // untriggered orders can not expire
+        if order.max_ts != 0 && node_type != NodeType::Trigger {
+            self.expiries
+                .lock()
+                .unwrap()
+                .insert((order.max_ts, user_account, order.order_id));
+        }
+
         self.exchange
             .add_market_indempotent(&market_type, market_index);
 
@@ -102,21 +150,174 @@ impl DLOB {
         }
     }
 
+    /// Remove an order from the DLOB, no-op if it does not exist
+    pub fn delete_order(&self, order: &Order, user_account: Pubkey) {
+        let market = match order.market_type {
+            MarketType::Perp => self.exchange.perp.get_mut(&order.market_index),
+            MarketType::Spot => self.exchange.spot.get_mut(&order.market_index),
+        };
+        let removed =
+            market.and_then(|mut market| market.remove_order(order.order_id, user_account));
+        if let Some(removed) = removed.filter(|n| n.get_order().max_ts != 0) {
+            self.expiries.lock().unwrap().remove(&(
+                removed.get_order().max_ts,
+                user_account,
+                order.order_id,
+            ));
+        }
+    }
+
+    /// Return orders expired at `now_ts` i.e. their `max_ts` has passed, earliest expiry first
+    ///
+    /// Keepers may cancel these orders on-chain
+    pub fn find_expired_nodes(&self, now_ts: i64) -> Vec<Node> {
+        let expired: Vec<(i64, Pubkey, u32)> = self
+            .expiries
+            .lock()
+            .unwrap()
+            .iter()
+            .take_while(|(max_ts, _, _)| *max_ts < now_ts)
+            .copied()
+            .collect();
+
+        expired
+            .into_iter()
+            .filter_map(|(max_ts, user_account, order_id)| {
+                self.exchange
+                    .get_node(&get_order_signature(order_id, user_account))
+                    .filter(|n| n.get_order().max_ts == max_ts)
+            })
+            .collect()
+    }
+
+    /// Remove orders expired at `now_ts` from the DLOB
+    ///
+    /// Returns the removed nodes
+    pub fn prune_expired(&self, now_ts: i64) -> Vec<Node> {
+        let expired = self.find_expired_nodes(now_ts);
+        for node in expired.iter() {
+            self.delete_order(node.get_order(), node.get_user_account());
+        }
+        // drop entries of orders replaced without going through `delete_order`
+        self.expiries
+            .lock()
+            .unwrap()
+            .retain(|(max_ts, _, _)| *max_ts >= now_ts);
+
+        expired
+    }
+
+    /// Insert an order into the DLOB, replacing any existing version of it
+    pub fn update_order(&self, order: &Order, user_account: Pubkey, slot: u64) {
+        self.delete_order(order, user_account);
+        self.insert_order(order, user_account, slot);
+    }
+
+    /// Apply the order changes of a user account update to the DLOB
+    ///
+    /// `old` the previous account state, if known
+    ///
+    /// `new` the latest account state
+    ///
+    /// Returns the order changes applied
+    pub fn update_user(
+        &self,
+        user_account: Pubkey,
+        old: Option<&User>,
+        new: &User,
+        slot: u64,
+    ) -> Vec<DLOBUpdate> {
+        let mut updates = vec![];
+        if let Some(old) = old {
+            for old_order in old.orders.iter().filter(|o| o.status == OrderStatus::Open) {
+                let is_open = new
+                    .orders
+                    .iter()
+                    .any(|o| o.status == OrderStatus::Open && o.order_id == old_order.order_id);
+                if !is_open {
+                    self.delete_order(old_order, user_account);
+                    updates.push(DLOBUpdate::OrderRemoved {
+                        market: (old_order.market_index, old_order.market_type).into(),
+                        slot,
+                        user_account,
+                        order_id: old_order.order_id,
+                    });
+                }
+            }
+        }
+
+        for order in new.orders.iter().filter(|o| o.status == OrderStatus::Open) {
+            let is_unchanged = old.is_some_and(|old| old.orders.iter().any(|o| o == order));
+            if !is_unchanged {
+                self.update_order(order, user_account, slot);
+                updates.push(DLOBUpdate::OrderAdded {
+                    market: (order.market_index, order.market_type).into(),
+                    slot,
+                    user_account,
+                    order: *order,
+                });
+            }
+        }
+
+        updates
+    }
+
+    /// Return the markets with orders in the DLOB
+    pub fn markets(&self) -> Vec<MarketId> {
+        let perp = self.exchange.perp.iter().map(|m| MarketId::perp(*m.key()));
+        let spot = self.exchange.spot.iter().map(|m| MarketId::spot(*m.key()));
+        perp.chain(spot).collect()
+    }
+
     pub fn get_order(&self, order_id: u32, user_account: Pubkey) -> Option<Order> {
         let order_signature = get_order_signature(order_id, user_account);
-        for order_list in self.exchange.get_order_lists() {
-            if let Some(node) = order_list.get_node(&order_signature) {
-                return Some(*node.get_order());
+        self.exchange
+            .get_node(&order_signature)
+            .map(|node| *node.get_order())
+    }
+
+    /// Capture all orders of the DLOB, as of `slot`
+    pub fn snapshot(&self, slot: u64) -> DLOBSnapshot {
+        let mut nodes = vec![];
+        for markets in self.exchange.iter() {
+            for market in markets.iter() {
+                nodes.extend(market.value().nodes().map(|node| SnapshotNode {
+                    node_type: node.get_node_type(),
+                    user_account: node.get_user_account(),
+                    order: *node.get_order(),
+                }));
             }
         }
 
-        None
+        DLOBSnapshot { slot, nodes }
+    }
+
+    /// Restore a DLOB from `snapshot`, orders keep their captured node types
+    pub fn from_snapshot(snapshot: &DLOBSnapshot) -> DLOB {
+        let dlob = DLOB::new();
+        for node in snapshot.nodes.iter() {
+            let (subtype, _) = get_node_subtype_and_type(&node.order, snapshot.slot);
+            dlob.insert_node(subtype, node.node_type, &node.order, node.user_account);
+        }
+        // node types are as of the snapshot slot
+        dlob._max_slot_for_resting_limit_orders
+            .store(snapshot.slot, Ordering::Relaxed);
+
+        dlob
     }
 
-    fn update_resting_limit_orders_for_market_type(&mut self, slot: u64, market_type: MarketType) {
-        let mut new_taking_asks: BinaryHeap<DirectionalNode> = BinaryHeap::new();
-        let mut new_taking_bids: BinaryHeap<DirectionalNode> = BinaryHeap::new();
+    /// Return a read-only view of the orders of `market`, if it has any
+    ///
+    /// The view holds a read lock on the market, drop it before modifying the DLOB
+    pub fn market_view(&self, market: MarketId) -> Option<MarketView<'_>> {
+        match market.kind {
+            MarketType::Perp => self.exchange.perp.get(&market.index),
+            MarketType::Spot => self.exchange.spot.get(&market.index),
+        }
+        .map(|market| MarketView { market })
+    }
 
+    fn update_resting_limit_orders_for_market_type(&self, slot: u64, market_type: MarketType) {
         let market = match market_type {
             MarketType::Perp => &self.exchange.perp,
             MarketType::Spot => &self.exchange.spot,
@@ -124,39 +325,33 @@ impl DLOB {
 
         for mut market_ref in market.iter_mut() {
             let market = market_ref.value_mut();
+            let (new_resting_bids, new_resting_asks) = market
+                .taking_limit_orders
+                .remove_where(|node| is_resting_limit_order(node.get_order(), slot));
 
-            for directional_node in market.taking_limit_orders.bids.iter() {
-                if is_resting_limit_order(directional_node.node.get_order(), slot) {
-                    market
-                        .resting_limit_orders
-                        .insert_bid(directional_node.node)
-                } else {
-                    new_taking_bids.push(*directional_node)
-                }
+            for mut node in new_resting_bids {
+                node.set_node_type(NodeType::RestingLimit);
+                market.resting_limit_orders.insert_bid(node);
             }
-
-            for directional_node in market.taking_limit_orders.asks.iter() {
-                if is_resting_limit_order(directional_node.node.get_order(), slot) {
-                    market
-                        .resting_limit_orders
-                        .insert_ask(directional_node.node);
-                } else {
-                    new_taking_asks.push(*directional_node);
-                }
+            for mut node in new_resting_asks {
+                node.set_node_type(NodeType::RestingLimit);
+                market.resting_limit_orders.insert_ask(node);
             }
-
-            market.taking_limit_orders.bids = new_taking_bids.clone();
-            market.taking_limit_orders.asks = new_taking_asks.clone();
         }
     }
 
-    pub fn update_resting_limit_orders(&mut self, slot: u64) {
-        if slot <= *self._max_slot_for_resting_limit_orders {
+    /// Progress order auctions to `slot`, taking limit orders whose auction completed become resting makers
+    ///
+    /// Taking order prices are derived from the slot so they always reflect the current auction price
+    pub fn update_resting_limit_orders(&self, slot: u64) {
+        if self
+            ._max_slot_for_resting_limit_orders
+            .fetch_max(slot, Ordering::Relaxed)
+            >= slot
+        {
             return;
         }
 
-        self._max_slot_for_resting_limit_orders = Arc::new(slot);
-
         self.update_resting_limit_orders_for_market_type(slot, MarketType::Perp);
         self.update_resting_limit_orders_for_market_type(slot, MarketType::Spot);
     }
@@ -168,122 +363,698 @@ impl DLOB {
         node_type: NodeType,
         market_index: u16,
     ) -> Vec<Node> {
-        let market = match market_type {
-            MarketType::Perp => self.exchange.perp.get_mut(&market_index).expect("market"),
-            MarketType::Spot => self.exchange.spot.get_mut(&market_index).expect("market"),
+        let Some(market) = self.market_view((market_index, market_type).into()) else {
+            return vec![];
         };
-        let mut order_list = market.get_order_list_for_node_type(node_type);
-
-        let mut best_orders: Vec<Node> = vec![];
 
         match sub_type {
-            SubType::Bid => {
-                while !order_list.bids_empty() {
-                    if let Some(node) = order_list.get_best_bid() {
-                        best_orders.push(node);
-                    }
-                }
-            }
-            SubType::Ask => {
-                while !order_list.asks_empty() {
-                    if let Some(node) = order_list.get_best_ask() {
-                        best_orders.push(node);
-                    }
-                }
-            }
+            SubType::Bid => market.bids(node_type).copied().collect(),
+            SubType::Ask => market.asks(node_type).copied().collect(),
             _ => unimplemented!(),
         }
-
-        best_orders
     }
 
     pub fn get_resting_limit_asks(
-        &mut self,
+        &self,
         slot: u64,
         market_type: MarketType,
         market_index: u16,
         oracle_price_data: OraclePriceData,
     ) -> Vec<Node> {
+        self.get_top_asks(
+            (market_index, market_type).into(),
+            usize::MAX,
+            slot,
+            oracle_price_data,
+        )
+    }
+
+    pub fn get_resting_limit_bids(
+        &self,
+        slot: u64,
+        market_type: MarketType,
+        market_index: u16,
+        oracle_price_data: OraclePriceData,
+    ) -> Vec<Node> {
+        self.get_top_bids(
+            (market_index, market_type).into(),
+            usize::MAX,
+            slot,
+            oracle_price_data,
+        )
+    }
+
+    /// Return the best `n` resting limit bids of `market`, best first
+    pub fn get_top_bids(
+        &self,
+        market: MarketId,
+        n: usize,
+        slot: u64,
+        oracle_price_data: OraclePriceData,
+    ) -> Vec<Node> {
+        self.update_resting_limit_orders(slot);
+        self.market_view(market)
+            .map(|market| {
+                market
+                    .resting_limit_bids(oracle_price_data, slot)
+                    .take(n)
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Return the best `n` resting limit asks of `market`, best first
+    pub fn get_top_asks(
+        &self,
+        market: MarketId,
+        n: usize,
+        slot: u64,
+        oracle_price_data: OraclePriceData,
+    ) -> Vec<Node> {
+        self.update_resting_limit_orders(slot);
+        self.market_view(market)
+            .map(|market| {
+                market
+                    .resting_limit_asks(oracle_price_data, slot)
+                    .take(n)
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Return vAMM liquidity of a perp market as `VAMMNode`s, best price first
+    ///
+    /// `direction` `Long` for the vAMM bids, `Short` for the vAMM asks
+    pub fn get_vamm_nodes(
+        perp_market: &PerpMarket,
+        direction: PositionDirection,
+        num_levels: usize,
+    ) -> Vec<Node> {
+        calculate_vamm_levels(&perp_market.amm, direction, num_levels)
+            .into_iter()
+            .map(|(price, size)| {
+                let order = Order {
+                    market_index: perp_market.market_index,
+                    market_type: MarketType::Perp,
+                    order_type: OrderType::Limit,
+                    direction,
+                    price,
+                    base_asset_amount: size,
+                    ..Order::default()
+                };
+                Node::VAMMNode(VAMMNode::new(order, price))
+            })
+            .collect()
+    }
+
+    /// Call `f` with the resting bids and asks of `market` merged with vAMM liquidity (perp markets only)
+    fn with_book<R>(
+        &self,
+        market: MarketId,
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+        perp_market: Option<&PerpMarket>,
+        f: impl FnOnce(&mut dyn Iterator<Item = &Node>, &mut dyn Iterator<Item = &Node>) -> R,
+    ) -> R {
         self.update_resting_limit_orders(slot);
 
-        let mut resting_limit_orders = self.get_best_orders(
-            market_type,
-            SubType::Ask,
-            NodeType::RestingLimit,
-            market_index,
+        let (vamm_bids, vamm_asks) = match perp_market.filter(|_| market.kind == MarketType::Perp) {
+            Some(perp_market) => (
+                Self::get_vamm_nodes(perp_market, PositionDirection::Long, VAMM_LEVELS),
+                Self::get_vamm_nodes(perp_market, PositionDirection::Short, VAMM_LEVELS),
+            ),
+            None => Default::default(),
+        };
+        let market = self.market_view(market);
+        // user orders go ahead of the vAMM at equal prices
+        let mut bids = MergeByPrice::new(
+            market
+                .iter()
+                .flat_map(|m| m.resting_limit_bids(oracle_price_data, slot)),
+            vamm_bids.iter(),
+            oracle_price_data,
+            slot,
+            SortDirection::Descending,
         );
-        let mut floating_limit_orders = self.get_best_orders(
-            market_type,
-            SubType::Ask,
-            NodeType::FloatingLimit,
-            market_index,
+        let mut asks = MergeByPrice::new(
+            market
+                .iter()
+                .flat_map(|m| m.resting_limit_asks(oracle_price_data, slot)),
+            vamm_asks.iter(),
+            oracle_price_data,
+            slot,
+            SortDirection::Ascending,
         );
 
-        let comparative = Box::new(
-            |node_a: &Node, node_b: &Node, slot: u64, oracle_price_data: OraclePriceData| {
-                node_a.get_price(oracle_price_data, slot)
-                    > node_b.get_price(oracle_price_data, slot)
+        f(&mut bids, &mut asks)
+    }
+
+    /// Return the L2 orderbook of `market`, aggregated by price level
+    ///
+    /// `depth` max. number of levels per side
+    ///
+    /// `perp_market` include the vAMM liquidity of this perp market
+    pub fn get_l2(
+        &self,
+        market: MarketId,
+        depth: usize,
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+        perp_market: Option<&PerpMarket>,
+    ) -> L2Orderbook {
+        let to_levels = |nodes: &mut dyn Iterator<Item = &Node>| {
+            let mut levels = Vec::<L2Level>::with_capacity(depth);
+            for node in nodes {
+                let price = node.get_price(oracle_price_data, slot) as i64;
+                let size = remaining_base_amount(node) as i64;
+                if let Some(level) = levels.last_mut().filter(|l| l.price == price) {
+                    level.size += size;
+                    continue;
+                }
+                if levels.len() == depth {
+                    break;
+                }
+                levels.push(L2Level { price, size });
+            }
+            levels
+        };
+
+        self.with_book(
+            market,
+            oracle_price_data,
+            slot,
+            perp_market,
+            |bids, asks| L2Orderbook {
+                bids: to_levels(bids),
+                asks: to_levels(asks),
+                slot,
+            },
+        )
+    }
+
+    /// Return the L3 orderbook of `market` i.e. every individual order
+    ///
+    /// `perp_market` include the vAMM liquidity of this perp market, with maker "vamm"
+    pub fn get_l3(
+        &self,
+        market: MarketId,
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+        perp_market: Option<&PerpMarket>,
+    ) -> L3Orderbook {
+        let to_levels = |nodes: &mut dyn Iterator<Item = &Node>| {
+            nodes
+                .map(|node| L3Level {
+                    price: node.get_price(oracle_price_data, slot) as i64,
+                    size: remaining_base_amount(node) as i64,
+                    maker: if node.is_vamm_node() {
+                        VAMM_MAKER.to_string()
+                    } else {
+                        node.get_user_account().to_string()
+                    },
+                    order_id: node.get_order().order_id as u64,
+                })
+                .collect()
+        };
+
+        self.with_book(
+            market,
+            oracle_price_data,
+            slot,
+            perp_market,
+            |bids, asks| L3Orderbook {
+                bids: to_levels(bids),
+                asks: to_levels(asks),
+                slot,
+            },
+        )
+    }
+
+    /// Simulate a taker order of `base_amount` hitting resting limit orders and vAMM liquidity (perps only)
+    ///
+    /// `market` the market account, providing the vAMM and base precision
+    ///
+    /// `fee_tier` the taker's fee tier e.g. `State::perp_fee_structure.fee_tiers[0]`,
+    /// for perps the market's `fee_adjustment` is applied on top
+    ///
+    /// The result lists the maker orders hit, best first
+    pub fn simulate_taker_fill(
+        &self,
+        market: FillMarket<'_>,
+        direction: PositionDirection,
+        base_amount: u64,
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+        fee_tier: &FeeTier,
+    ) -> TakerFillSimulation {
+        let (market_id, perp_market, base_precision, fee_adjustment) = match market {
+            FillMarket::Perp(perp_market) => (
+                MarketId::perp(perp_market.market_index),
+                Some(perp_market),
+                BASE_PRECISION_U64 as u128,
+                perp_market.fee_adjustment,
+            ),
+            FillMarket::Spot(spot_market) => (
+                MarketId::spot(spot_market.market_index),
+                None,
+                10_u128.pow(spot_market.decimals),
+                0,
+            ),
+        };
+
+        let mut simulation = TakerFillSimulation::default();
+        // sum of price * base, in PRICE_PRECISION * base precision
+        let mut notional: u128 = 0;
+        self.with_book(
+            market_id,
+            oracle_price_data,
+            slot,
+            perp_market,
+            |bids, asks| {
+                let makers = match direction {
+                    PositionDirection::Long => asks,
+                    PositionDirection::Short => bids,
+                };
+                for node in makers {
+                    let remaining = base_amount - simulation.base_filled;
+                    if remaining == 0 {
+                        break;
+                    }
+                    let size = remaining_base_amount(node).min(remaining);
+                    if size == 0 {
+                        continue;
+                    }
+                    let price = node.get_price(oracle_price_data, slot);
+                    simulation.base_filled += size;
+                    simulation.worst_price = price;
+                    notional += price as u128 * size as u128;
+                    if node.is_vamm_node() {
+                        simulation.vamm_base_filled += size;
+                    } else {
+                        simulation.makers.push(MakerFill {
+                            user_account: node.get_user_account(),
+                            order_id: node.get_order().order_id,
+                            price,
+                            base_amount: size,
+                        });
+                    }
+                }
             },
         );
 
-        let mut all_orders = vec![];
-        all_orders.append(&mut resting_limit_orders);
-        all_orders.append(&mut floating_limit_orders);
+        if simulation.base_filled > 0 {
+            simulation.average_price = (notional / simulation.base_filled as u128) as u64;
+            simulation.quote_amount = (notional / base_precision) as u64;
+            simulation.fee = calculate_taker_fee(simulation.quote_amount, fee_tier, fee_adjustment);
+        }
+
+        simulation
+    }
 
-        all_orders.sort_by(|a, b| {
-            if comparative(a, b, slot, oracle_price_data) {
-                std::cmp::Ordering::Greater
-            } else {
-                std::cmp::Ordering::Less
-            }
-        });
+    /// Return the taking bids of `market` at `slot`, oldest first
+    ///
+    /// i.e. market orders, taking limit orders and floating limit orders with an auction in progress.
+    /// Node prices follow the auction as the slot progresses, see `DLOBNode::get_price`
+    pub fn get_taking_bids(&self, market: MarketId, slot: u64) -> Vec<Node> {
+        self.get_taking_orders(market, SubType::Bid, slot)
+    }
 
-        all_orders
+    /// Return the taking asks of `market` at `slot`, oldest first
+    ///
+    /// i.e. market orders, taking limit orders and floating limit orders with an auction in progress.
+    /// Node prices follow the auction as the slot progresses, see `DLOBNode::get_price`
+    pub fn get_taking_asks(&self, market: MarketId, slot: u64) -> Vec<Node> {
+        self.get_taking_orders(market, SubType::Ask, slot)
     }
 
-    pub fn get_resting_limit_bids(
-        &mut self,
+    fn get_taking_orders(&self, market: MarketId, sub_type: SubType, slot: u64) -> Vec<Node> {
+        self.update_resting_limit_orders(slot);
+        let Some(market) = self.market_view(market) else {
+            return vec![];
+        };
+        let (market_orders, taking_limit_orders, floating_limit_orders) = match sub_type {
+            SubType::Bid => (
+                market.bids(NodeType::Market),
+                market.bids(NodeType::TakingLimit),
+                market.bids(NodeType::FloatingLimit),
+            ),
+            _ => (
+                market.asks(NodeType::Market),
+                market.asks(NodeType::TakingLimit),
+                market.asks(NodeType::FloatingLimit),
+            ),
+        };
+        let mut nodes: Vec<Node> = market_orders
+            .chain(taking_limit_orders)
+            .chain(floating_limit_orders.filter(|n| !is_resting_limit_order(n.get_order(), slot)))
+            .copied()
+            .collect();
+        nodes.sort_by_key(|node| node.get_order().slot);
+        nodes
+    }
+
+    /// Find orders of `market` that can be filled
+    ///
+    /// - taking orders crossing resting limit orders, or the fallback liquidity once their auction is complete
+    /// - resting limit orders crossing each other, the newer order is the taker
+    ///
+    /// `fallback_bid`/`fallback_ask` best price of the fallback liquidity e.g. vAMM bid/ask
+    pub fn find_nodes_to_fill(
+        &self,
+        market: MarketId,
         slot: u64,
-        market_type: MarketType,
-        market_index: u16,
         oracle_price_data: OraclePriceData,
-    ) -> Vec<Node> {
-        self.update_resting_limit_orders(slot);
+        fallback_bid: Option<u64>,
+        fallback_ask: Option<u64>,
+    ) -> Vec<NodeToFill> {
+        let resting_bids =
+            self.get_resting_limit_bids(slot, market.kind, market.index, oracle_price_data);
+        let resting_asks =
+            self.get_resting_limit_asks(slot, market.kind, market.index, oracle_price_data);
+
+        let mut nodes_to_fill = vec![];
+        for (taker_side, makers, fallback_price) in [
+            (SubType::Bid, &resting_asks, fallback_ask),
+            (SubType::Ask, &resting_bids, fallback_bid),
+        ] {
+            let crosses = |taker_price: u64, maker_price: u64| match taker_side {
+                SubType::Bid => maker_price <= taker_price,
+                _ => maker_price >= taker_price,
+            };
+
+            for taker in self.get_taking_orders(market, taker_side, slot) {
+                let order = taker.get_order();
+                if taker.is_base_filled() {
+                    continue;
+                }
+                // market orders without auction or limit price take the fallback price
+                let taker_price = if order.price == 0
+                    && order.oracle_price_offset == 0
+                    && !has_auction_price(order, slot)
+                {
+                    match fallback_price {
+                        Some(price) => price,
+                        None => continue,
+                    }
+                } else {
+                    taker.get_price(oracle_price_data, slot)
+                };
+
+                let mut remaining = remaining_base_amount(&taker);
+                let mut maker_nodes = vec![];
+                for maker in makers.iter() {
+                    if remaining == 0
+                        || !crosses(taker_price, maker.get_price(oracle_price_data, slot))
+                    {
+                        break;
+                    }
+                    if maker.is_base_filled()
+                        || maker.get_user_account() == taker.get_user_account()
+                    {
+                        continue;
+                    }
+                    maker_nodes.push(*maker);
+                    remaining = remaining.saturating_sub(remaining_base_amount(maker));
+                }
 
-        let mut resting_limit_orders = self.get_best_orders(
-            market_type,
-            SubType::Bid,
-            NodeType::RestingLimit,
-            market_index,
-        );
-        let mut floating_limit_orders = self.get_best_orders(
-            market_type,
-            SubType::Bid,
-            NodeType::FloatingLimit,
-            market_index,
-        );
+                if !maker_nodes.is_empty() {
+                    nodes_to_fill.push(NodeToFill {
+                        node: taker,
+                        maker_nodes,
+                    });
+                } else if is_auction_complete(order, slot)
+                    && fallback_price.is_some_and(|price| crosses(taker_price, price))
+                {
+                    nodes_to_fill.push(NodeToFill {
+                        node: taker,
+                        maker_nodes,
+                    });
+                }
+            }
+        }
 
-        let comparative = Box::new(
-            |node_a: &Node, node_b: &Node, slot: u64, oracle_price_data: OraclePriceData| {
-                node_a.get_price(oracle_price_data, slot)
-                    < node_b.get_price(oracle_price_data, slot)
-            },
-        );
+        nodes_to_fill.extend(Self::find_crossing_resting_limit_orders(
+            &resting_bids,
+            &resting_asks,
+            oracle_price_data,
+            slot,
+        ));
 
-        let mut all_orders = vec![];
-        all_orders.append(&mut resting_limit_orders);
-        all_orders.append(&mut floating_limit_orders);
+        nodes_to_fill
+    }
 
-        all_orders.sort_by(|a, b| {
-            if comparative(a, b, slot, oracle_price_data) {
-                std::cmp::Ordering::Greater
-            } else {
-                std::cmp::Ordering::Less
+    /// Find resting limit orders crossing each other, the newer order is the taker
+    fn find_crossing_resting_limit_orders(
+        bids: &[Node],
+        asks: &[Node],
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+    ) -> Vec<NodeToFill> {
+        let mut nodes_to_fill = vec![];
+        for ask in asks.iter().filter(|n| !n.is_base_filled()) {
+            let ask_price = ask.get_price(oracle_price_data, slot);
+            for bid in bids.iter().filter(|n| !n.is_base_filled()) {
+                if bid.get_price(oracle_price_data, slot) < ask_price {
+                    break;
+                }
+                if bid.get_user_account() == ask.get_user_account() {
+                    continue;
+                }
+                let (taker, maker) = if bid.get_order().slot > ask.get_order().slot {
+                    (bid, ask)
+                } else {
+                    (ask, bid)
+                };
+                nodes_to_fill.push(NodeToFill {
+                    node: *taker,
+                    maker_nodes: vec![*maker],
+                });
             }
-        });
+        }
+
+        nodes_to_fill
+    }
+
+    /// Find trigger orders of `market` that can be triggered at `oracle_price`
+    pub fn find_nodes_to_trigger(&self, market: MarketId, oracle_price: i64) -> Vec<Node> {
+        let Some(market) = self.market_view(market) else {
+            return vec![];
+        };
+        // trigger above orders are stored as bids (lowest first), trigger below as asks (highest first)
+        let trigger_above = market
+            .bids(NodeType::Trigger)
+            .take_while(|n| oracle_price > n.get_order().trigger_price as i64);
+        let trigger_below = market
+            .asks(NodeType::Trigger)
+            .take_while(|n| oracle_price < n.get_order().trigger_price as i64);
+
+        trigger_above.chain(trigger_below).copied().collect()
+    }
+}
+
+/// Taker fee on `quote_amount`, as the program computes it
+///
+/// `fee_adjustment` scales the fee tier by a percentage e.g. -50 halves the fee
+fn calculate_taker_fee(quote_amount: u64, fee_tier: &FeeTier, fee_adjustment: i16) -> u64 {
+    let fee = (quote_amount as u128 * fee_tier.fee_numerator as u128)
+        .div_ceil(fee_tier.fee_denominator.max(1) as u128);
+    let adjusted = if fee_adjustment < 0 {
+        fee.saturating_sub(fee * fee_adjustment.unsigned_abs() as u128 / 100)
+    } else {
+        fee + (fee * fee_adjustment as u128).div_ceil(100)
+    };
+
+    adjusted as u64
+}
+
+/// Market account of a taker fill simulation
+#[derive(Copy, Clone, Debug)]
+pub enum FillMarket<'a> {
+    Perp(&'a PerpMarket),
+    Spot(&'a SpotMarket),
+}
+
+/// A maker order hit by a simulated taker fill
+///
+/// `user_account` is the maker to provide as `maker_info` to `TransactionBuilder::place_and_take`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MakerFill {
+    pub user_account: Pubkey,
+    pub order_id: u32,
+    /// fill price (PRICE_PRECISION)
+    pub price: u64,
+    /// base amount filled against this order
+    pub base_amount: u64,
+}
+
+/// Expected outcome of a taker fill, see `DLOB::simulate_taker_fill`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TakerFillSimulation {
+    /// base amount filled, less than requested if the book lacks liquidity
+    pub base_filled: u64,
+    /// quote amount exchanged excl. fees (QUOTE_PRECISION)
+    pub quote_amount: u64,
+    /// average fill price (PRICE_PRECISION)
+    pub average_price: u64,
+    /// price of the last level hit (PRICE_PRECISION)
+    pub worst_price: u64,
+    /// taker fee (QUOTE_PRECISION)
+    pub fee: u64,
+    /// maker orders hit, best price first
+    pub makers: Vec<MakerFill>,
+    /// base amount filled against the vAMM
+    pub vamm_base_filled: u64,
+}
+
+/// A change to the orders of a DLOB market
+#[derive(Clone, Debug, PartialEq)]
+pub enum DLOBUpdate {
+    /// An order was placed or modified, replacing any previous version of it
+    OrderAdded {
+        market: MarketId,
+        slot: u64,
+        user_account: Pubkey,
+        order: Order,
+    },
+    /// An order left the book i.e. it was filled, cancelled or expired
+    OrderRemoved {
+        market: MarketId,
+        slot: u64,
+        user_account: Pubkey,
+        order_id: u32,
+    },
+    /// The best resting limit bid or ask price changed
+    ///
+    /// Floating (oracle offset) orders are excluded as their price moves with the oracle
+    BestBidAskChanged {
+        market: MarketId,
+        slot: u64,
+        best_bid: Option<u64>,
+        best_ask: Option<u64>,
+    },
+}
+
+impl DLOBUpdate {
+    /// The market of the update
+    pub fn market(&self) -> MarketId {
+        match self {
+            Self::OrderAdded { market, .. }
+            | Self::OrderRemoved { market, .. }
+            | Self::BestBidAskChanged { market, .. } => *market,
+        }
+    }
+
+    /// The slot of the update
+    pub fn slot(&self) -> u64 {
+        match self {
+            Self::OrderAdded { slot, .. }
+            | Self::OrderRemoved { slot, .. }
+            | Self::BestBidAskChanged { slot, .. } => *slot,
+        }
+    }
+}
+
+/// Read-only view of the orders of one DLOB market
+///
+/// Iterators are lazy and read the orders in place, without cloning or sorting
+pub struct MarketView<'a> {
+    market: Ref<'a, u16, Market>,
+}
+
+impl MarketView<'_> {
+    /// Iterate the bids of `node_type` orders, best first
+    pub fn bids(&self, node_type: NodeType) -> impl Iterator<Item = &Node> + '_ {
+        self.market.get_order_list_for_node_type(node_type).bids()
+    }
+
+    /// Iterate the asks of `node_type` orders, best first
+    pub fn asks(&self, node_type: NodeType) -> impl Iterator<Item = &Node> + '_ {
+        self.market.get_order_list_for_node_type(node_type).asks()
+    }
+
+    /// Iterate the resting and floating limit bids at the given oracle price, highest price first
+    ///
+    /// Taking limit orders only rest once `DLOB::update_resting_limit_orders` reaches their slot,
+    /// floating limit orders rest once their auction completes
+    pub fn resting_limit_bids(
+        &self,
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+    ) -> impl Iterator<Item = &Node> + '_ {
+        MergeByPrice::new(
+            self.market.resting_limit_orders.bids(),
+            self.market
+                .floating_limit_orders
+                .bids()
+                .filter(move |n| is_resting_limit_order(n.get_order(), slot)),
+            oracle_price_data,
+            slot,
+            SortDirection::Descending,
+        )
+    }
+
+    /// Iterate the resting and floating limit asks at the given oracle price, lowest price first
+    ///
+    /// Taking limit orders only rest once `DLOB::update_resting_limit_orders` reaches their slot,
+    /// floating limit orders rest once their auction completes
+    pub fn resting_limit_asks(
+        &self,
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+    ) -> impl Iterator<Item = &Node> + '_ {
+        MergeByPrice::new(
+            self.market.resting_limit_orders.asks(),
+            self.market
+                .floating_limit_orders
+                .asks()
+                .filter(move |n| is_resting_limit_order(n.get_order(), slot)),
+            oracle_price_data,
+            slot,
+            SortDirection::Ascending,
+        )
+    }
 
-        all_orders
+    /// Return the best fixed price resting limit (bid, ask) prices, excludes floating orders
+    pub fn best_fixed_prices(&self) -> (Option<u64>, Option<u64>) {
+        let price = |node: &Node| node.get_order().price;
+        (
+            self.bids(NodeType::RestingLimit).next().map(price),
+            self.asks(NodeType::RestingLimit).next().map(price),
+        )
     }
+
+    /// Return the best resting limit bid at the given oracle price
+    pub fn best_bid(&self, oracle_price_data: OraclePriceData, slot: u64) -> Option<&Node> {
+        self.resting_limit_bids(oracle_price_data, slot).next()
+    }
+
+    /// Return the best resting limit ask at the given oracle price
+    pub fn best_ask(&self, oracle_price_data: OraclePriceData, slot: u64) -> Option<&Node> {
+        self.resting_limit_asks(oracle_price_data, slot).next()
+    }
+}
+
+/// A taker order to fill along with the maker orders it crosses
+///
+/// No maker nodes means the taker crosses the fallback liquidity e.g. the vAMM
+#[derive(Clone, Debug)]
+pub struct NodeToFill {
+    pub node: Node,
+    pub maker_nodes: Vec<Node>,
+}
+
+/// number of vAMM levels included in L2/L3 books
+const VAMM_LEVELS: usize = 10;
+/// maker of vAMM levels in L3 books
+const VAMM_MAKER: &str = "vamm";
+
+fn remaining_base_amount(node: &Node) -> u64 {
+    let order = node.get_order();
+    order
+        .base_asset_amount
+        .saturating_sub(order.base_asset_amount_filled)
 }
 
 impl Default for DLOB {
@@ -306,8 +1077,13 @@ impl Event for DLOB {
 mod tests {
     use super::*;
     use drift::{
-        math::constants::PRICE_PRECISION_U64,
-        state::user::{Order, OrderType},
+        math::constants::{
+            AMM_RESERVE_PRECISION, PEG_PRECISION, PRICE_PRECISION_U64, QUOTE_PRECISION_U64,
+        },
+        state::{
+            perp_market::AMM,
+            user::{Order, OrderTriggerCondition, OrderType},
+        },
     };
     use solana_sdk::pubkey::Pubkey;
 
@@ -437,9 +1213,67 @@ mod tests {
         assert_eq!(best_orders[4].get_order().slot, 5);
     }
 
+    #[test]
+    fn test_dlob_update_user() {
+        let dlob = DLOB::new();
+        let user_account = Pubkey::new_unique();
+
+        let mut user = User::default();
+        for (idx, order_id) in (1..=3).enumerate() {
+            user.orders[idx] = Order {
+                order_id,
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                market_type: MarketType::Perp,
+                price: order_id as u64 * PRICE_PRECISION_U64,
+                ..Order::default()
+            };
+        }
+        let updates = dlob.update_user(user_account, None, &user, 1);
+        assert_eq!(updates.len(), 3);
+        assert_eq!(dlob.size(), (3, 0));
+
+        // order 1 cancelled, order 2 modified, order 4 placed
+        let mut new_user = user;
+        new_user.orders[0] = Order::default();
+        new_user.orders[1].price = 5 * PRICE_PRECISION_U64;
+        new_user.orders[3] = Order {
+            order_id: 4,
+            status: OrderStatus::Open,
+            market_type: MarketType::Spot,
+            market_index: 1,
+            ..Order::default()
+        };
+        let updates = dlob.update_user(user_account, Some(&user), &new_user, 2);
+        assert_eq!(
+            updates[0],
+            DLOBUpdate::OrderRemoved {
+                market: MarketId::perp(0),
+                slot: 2,
+                user_account,
+                order_id: 1
+            }
+        );
+        assert_eq!(updates.len(), 3);
+        assert_eq!(updates[2].market(), MarketId::spot(1));
+
+        assert_eq!(dlob.size(), (2, 1));
+        assert!(dlob.get_order(1, user_account).is_none());
+        assert_eq!(
+            dlob.get_order(2, user_account).unwrap().price,
+            5 * PRICE_PRECISION_U64
+        );
+        assert!(dlob.get_order(3, user_account).is_some());
+        assert!(dlob.get_order(4, user_account).is_some());
+
+        // all orders closed
+        dlob.update_user(user_account, Some(&new_user), &User::default(), 3);
+        assert_eq!(dlob.size(), (0, 0));
+    }
+
     #[test]
     fn test_update_resting_limit_orders() {
-        let mut dlob = DLOB::new();
+        let dlob = DLOB::new();
 
         let user_account = Pubkey::new_unique();
         let order_1 = Order {
@@ -457,7 +1291,7 @@ mod tests {
         let markets_for_market_type = dlob.exchange.perp.clone();
         let market = markets_for_market_type.get(&0).unwrap();
 
-        assert_eq!(market.taking_limit_orders.bids.len(), 1);
+        assert_eq!(market.taking_limit_orders.bids().count(), 1);
 
         let slot = 5;
 
@@ -469,13 +1303,13 @@ mod tests {
         let markets_for_market_type = dlob.exchange.perp.clone();
         let market = markets_for_market_type.get(&0).unwrap();
 
-        assert_eq!(market.taking_limit_orders.bids.len(), 0);
-        assert_eq!(market.resting_limit_orders.bids.len(), 1);
+        assert_eq!(market.taking_limit_orders.bids().count(), 0);
+        assert_eq!(market.resting_limit_orders.bids().count(), 1);
     }
 
     #[test]
     fn test_get_resting_limit_asks() {
-        let mut dlob = DLOB::new();
+        let dlob = DLOB::new();
 
         let v_ask = 15;
         let v_bid = 10;
@@ -569,7 +1403,7 @@ mod tests {
 
     #[test]
     fn test_get_resting_limit_bids() {
-        let mut dlob = DLOB::new();
+        let dlob = DLOB::new();
 
         let v_ask = 15;
         let v_bid = 10;
@@ -660,4 +1494,516 @@ mod tests {
         assert_eq!(resting_limit_bids[1].get_order().order_id, 2);
         assert_eq!(resting_limit_bids[2].get_order().order_id, 1);
     }
+
+    #[test]
+    fn test_market_view() {
+        let dlob = DLOB::new();
+        let oracle_price_data = OraclePriceData {
+            price: 20 * PRICE_PRECISION_U64 as i64,
+            confidence: 1,
+            delay: 0,
+            has_sufficient_number_of_data_points: true,
+        };
+        let user_account = Pubkey::new_unique();
+        let bid = |order_id: u32, price: u64, oracle_price_offset: i32| Order {
+            order_id,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            market_type: MarketType::Perp,
+            direction: PositionDirection::Long,
+            price,
+            oracle_price_offset,
+            base_asset_amount: 1_000,
+            post_only: true,
+            ..Order::default()
+        };
+        dlob.insert_order(&bid(1, 19 * PRICE_PRECISION_U64, 0), user_account, 1);
+        dlob.insert_order(&bid(2, 18 * PRICE_PRECISION_U64, 0), user_account, 1);
+        // floating bid at oracle - 0.5
+        dlob.insert_order(
+            &bid(3, 0, -(PRICE_PRECISION_U64 as i32) / 2),
+            user_account,
+            1,
+        );
+
+        let order_ids = |nodes: Vec<Node>| {
+            nodes
+                .iter()
+                .map(|n| n.get_order().order_id)
+                .collect::<Vec<u32>>()
+        };
+        let top_bids = dlob.get_top_bids(MarketId::perp(0), 2, 2, oracle_price_data);
+        assert_eq!(order_ids(top_bids), vec![3, 1]);
+        // reads are non-destructive
+        let top_bids = dlob.get_top_bids(MarketId::perp(0), 5, 2, oracle_price_data);
+        assert_eq!(order_ids(top_bids), vec![3, 1, 2]);
+
+        // floating bid moves behind the resting bid as the oracle price falls
+        let oracle_price_data = OraclePriceData {
+            price: 19 * PRICE_PRECISION_U64 as i64,
+            ..oracle_price_data
+        };
+        let market = dlob.market_view(MarketId::perp(0)).unwrap();
+        assert_eq!(
+            market
+                .best_bid(oracle_price_data, 2)
+                .unwrap()
+                .get_order()
+                .order_id,
+            1
+        );
+        assert_eq!(market.resting_limit_bids(oracle_price_data, 2).count(), 3);
+        assert!(market.best_ask(oracle_price_data, 2).is_none());
+        assert!(dlob.market_view(MarketId::spot(1)).is_none());
+    }
+
+    #[test]
+    fn test_prune_expired() {
+        let dlob = DLOB::new();
+        let user_account = Pubkey::new_unique();
+        for (order_id, max_ts) in [(1, 200), (2, 100), (3, 0)] {
+            let order = Order {
+                order_id,
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                market_type: MarketType::Perp,
+                price: PRICE_PRECISION_U64,
+                post_only: true,
+                max_ts,
+                ..Order::default()
+            };
+            dlob.insert_order(&order, user_account, 1);
+        }
+
+        assert!(dlob.find_expired_nodes(100).is_empty());
+        let expired = dlob.find_expired_nodes(201);
+        assert_eq!(expired.len(), 2);
+        assert_eq!(expired[0].get_order().order_id, 2);
+
+        let pruned = dlob.prune_expired(150);
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].get_order().order_id, 2);
+        assert!(dlob.get_order(2, user_account).is_none());
+        assert_eq!(dlob.size(), (2, 0));
+
+        // cancelled orders leave the expiry index
+        dlob.delete_order(&dlob.get_order(1, user_account).unwrap(), user_account);
+        assert!(dlob.find_expired_nodes(i64::MAX).is_empty());
+        assert!(dlob.get_order(3, user_account).is_some());
+
+        // untriggered orders do not expire
+        let trigger_order = Order {
+            order_id: 4,
+            status: OrderStatus::Open,
+            order_type: OrderType::TriggerLimit,
+            market_type: MarketType::Perp,
+            price: PRICE_PRECISION_U64,
+            trigger_condition: OrderTriggerCondition::Above,
+            trigger_price: 2 * PRICE_PRECISION_U64,
+            max_ts: 100,
+            ..Order::default()
+        };
+        dlob.insert_order(&trigger_order, user_account, 1);
+        assert!(dlob.prune_expired(i64::MAX).is_empty());
+        assert!(dlob.get_order(4, user_account).is_some());
+    }
+
+    #[test]
+    fn test_auction_progression() {
+        let dlob = DLOB::new();
+        let oracle_price_data = OraclePriceData {
+            price: 20 * PRICE_PRECISION_U64 as i64,
+            confidence: 1,
+            delay: 0,
+            has_sufficient_number_of_data_points: true,
+        };
+        let user_account = Pubkey::new_unique();
+        let auction_order = Order {
+            order_id: 1,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            market_type: MarketType::Perp,
+            direction: PositionDirection::Long,
+            slot: 1,
+            price: 20 * PRICE_PRECISION_U64,
+            auction_duration: 10,
+            auction_start_price: 10 * PRICE_PRECISION_U64 as i64,
+            auction_end_price: 20 * PRICE_PRECISION_U64 as i64,
+            base_asset_amount: 1_000,
+            ..Order::default()
+        };
+        dlob.insert_order(&auction_order, user_account, 1);
+        // floating limit order, auction from oracle - 1 to oracle
+        dlob.insert_order(
+            &Order {
+                order_id: 2,
+                price: 0,
+                oracle_price_offset: 1,
+                auction_start_price: -(PRICE_PRECISION_U64 as i64),
+                auction_end_price: 0,
+                ..auction_order
+            },
+            user_account,
+            1,
+        );
+
+        let taking_bids = dlob.get_taking_bids(MarketId::perp(0), 6);
+        assert_eq!(taking_bids.len(), 2);
+        assert_eq!(
+            taking_bids[0].get_price(oracle_price_data, 6),
+            15 * PRICE_PRECISION_U64
+        );
+        assert_eq!(
+            taking_bids[1].get_price(oracle_price_data, 6),
+            39 * PRICE_PRECISION_U64 / 2
+        );
+        assert!(dlob
+            .get_resting_limit_bids(6, MarketType::Perp, 0, oracle_price_data)
+            .is_empty());
+
+        // auctions complete, orders rest at their limit price
+        assert!(dlob.get_taking_bids(MarketId::perp(0), 12).is_empty());
+        let resting_bids = dlob.get_resting_limit_bids(12, MarketType::Perp, 0, oracle_price_data);
+        assert_eq!(resting_bids.len(), 2);
+        assert_eq!(resting_bids[0].get_order().order_id, 2);
+        assert_eq!(
+            resting_bids[0].get_price(oracle_price_data, 12),
+            20 * PRICE_PRECISION_U64 + 1
+        );
+        assert_eq!(resting_bids[1].get_node_type(), NodeType::RestingLimit);
+        assert_eq!(
+            resting_bids[1].get_price(oracle_price_data, 12),
+            20 * PRICE_PRECISION_U64
+        );
+    }
+
+    #[test]
+    fn test_simulate_taker_fill() {
+        let dlob = DLOB::new();
+        let oracle_price_data = OraclePriceData {
+            price: 20 * PRICE_PRECISION_U64 as i64,
+            confidence: 1,
+            delay: 0,
+            has_sufficient_number_of_data_points: true,
+        };
+        let maker = Pubkey::new_unique();
+        for (order_id, price) in [(1, 22), (2, 21)] {
+            let order = Order {
+                order_id,
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                market_type: MarketType::Perp,
+                direction: PositionDirection::Short,
+                price: price * PRICE_PRECISION_U64,
+                base_asset_amount: BASE_PRECISION_U64,
+                post_only: true,
+                ..Order::default()
+            };
+            dlob.insert_order(&order, maker, 1);
+        }
+        let perp_market = PerpMarket::default();
+        let fee_tier = FeeTier {
+            fee_numerator: 1,
+            fee_denominator: 1_000,
+            ..FeeTier::default()
+        };
+
+        let simulation = dlob.simulate_taker_fill(
+            FillMarket::Perp(&perp_market),
+            PositionDirection::Long,
+            3 * BASE_PRECISION_U64 / 2,
+            oracle_price_data,
+            2,
+            &fee_tier,
+        );
+        assert_eq!(simulation.base_filled, 3 * BASE_PRECISION_U64 / 2);
+        assert_eq!(simulation.quote_amount, 32 * QUOTE_PRECISION_U64);
+        assert_eq!(simulation.worst_price, 22 * PRICE_PRECISION_U64);
+        assert_eq!(simulation.average_price, 21_333_333);
+        assert_eq!(simulation.fee, 32_000);
+        assert_eq!(simulation.vamm_base_filled, 0);
+        assert_eq!(
+            simulation.makers,
+            vec![
+                MakerFill {
+                    user_account: maker,
+                    order_id: 2,
+                    price: 21 * PRICE_PRECISION_U64,
+                    base_amount: BASE_PRECISION_U64,
+                },
+                MakerFill {
+                    user_account: maker,
+                    order_id: 1,
+                    price: 22 * PRICE_PRECISION_U64,
+                    base_amount: BASE_PRECISION_U64 / 2,
+                },
+            ]
+        );
+
+        // no bids to hit
+        let simulation = dlob.simulate_taker_fill(
+            FillMarket::Perp(&perp_market),
+            PositionDirection::Short,
+            BASE_PRECISION_U64,
+            oracle_price_data,
+            2,
+            &fee_tier,
+        );
+        assert_eq!(simulation, TakerFillSimulation::default());
+    }
+
+    #[test]
+    fn test_simulate_taker_fill_fee_adjustment() {
+        let dlob = DLOB::new();
+        let oracle_price_data = OraclePriceData {
+            price: 20 * PRICE_PRECISION_U64 as i64,
+            confidence: 1,
+            delay: 0,
+            has_sufficient_number_of_data_points: true,
+        };
+        let order = Order {
+            order_id: 1,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            market_type: MarketType::Perp,
+            direction: PositionDirection::Short,
+            price: 20 * PRICE_PRECISION_U64,
+            base_asset_amount: BASE_PRECISION_U64,
+            post_only: true,
+            ..Order::default()
+        };
+        dlob.insert_order(&order, Pubkey::new_unique(), 1);
+        let fee_tier = FeeTier {
+            fee_numerator: 1,
+            fee_denominator: 1_000,
+            ..FeeTier::default()
+        };
+
+        for (fee_adjustment, fee) in [(0, 20_000), (-50, 10_000), (-33, 13_400), (25, 25_000)] {
+            let perp_market = PerpMarket {
+                fee_adjustment,
+                ..PerpMarket::default()
+            };
+            let simulation = dlob.simulate_taker_fill(
+                FillMarket::Perp(&perp_market),
+                PositionDirection::Long,
+                BASE_PRECISION_U64,
+                oracle_price_data,
+                2,
+                &fee_tier,
+            );
+            assert_eq!(simulation.quote_amount, 20 * QUOTE_PRECISION_U64);
+            assert_eq!(simulation.fee, fee, "fee_adjustment: {fee_adjustment}");
+        }
+    }
+
+    #[test]
+    fn test_get_l2_and_l3() {
+        let dlob = DLOB::new();
+        let oracle_price_data = OraclePriceData {
+            price: 20 * PRICE_PRECISION_U64 as i64,
+            confidence: 1,
+            delay: 0,
+            has_sufficient_number_of_data_points: true,
+        };
+        let maker = Pubkey::new_unique();
+        let resting_order = |order_id: u32, direction: PositionDirection, price: u64| Order {
+            order_id,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            market_type: MarketType::Perp,
+            direction,
+            price,
+            base_asset_amount: 1_000,
+            post_only: true,
+            ..Order::default()
+        };
+        for (order_id, direction, price) in [
+            (1, PositionDirection::Long, 19 * PRICE_PRECISION_U64),
+            (2, PositionDirection::Long, 19 * PRICE_PRECISION_U64),
+            (3, PositionDirection::Long, 18 * PRICE_PRECISION_U64),
+            (4, PositionDirection::Short, 21 * PRICE_PRECISION_U64),
+            (5, PositionDirection::Short, 22 * PRICE_PRECISION_U64),
+        ] {
+            dlob.insert_order(&resting_order(order_id, direction, price), maker, 1);
+        }
+
+        let l2 = dlob.get_l2(MarketId::perp(0), 10, oracle_price_data, 2, None);
+        assert_eq!(
+            l2.bids,
+            vec![
+                L2Level {
+                    price: 19 * PRICE_PRECISION_U64 as i64,
+                    size: 2_000
+                },
+                L2Level {
+                    price: 18 * PRICE_PRECISION_U64 as i64,
+                    size: 1_000
+                },
+            ]
+        );
+        assert_eq!(l2.asks.len(), 2);
+        assert_eq!(l2.asks[0].price, 21 * PRICE_PRECISION_U64 as i64);
+
+        let l2 = dlob.get_l2(MarketId::perp(0), 1, oracle_price_data, 2, None);
+        assert_eq!(l2.bids.len(), 1);
+        assert_eq!(l2.asks.len(), 1);
+
+        let l3 = dlob.get_l3(MarketId::perp(0), oracle_price_data, 2, None);
+        assert_eq!(l3.bids.len(), 3);
+        assert_eq!(l3.asks[1].order_id, 5);
+        assert_eq!(l3.asks[1].maker, maker.to_string());
+
+        // vAMM liquidity is merged in price order
+        let reserve = 1_000 * AMM_RESERVE_PRECISION;
+        let perp_market = PerpMarket {
+            amm: AMM {
+                base_asset_reserve: reserve,
+                quote_asset_reserve: reserve,
+                bid_base_asset_reserve: reserve,
+                bid_quote_asset_reserve: reserve,
+                ask_base_asset_reserve: reserve,
+                ask_quote_asset_reserve: reserve,
+                max_base_asset_reserve: 2 * reserve,
+                min_base_asset_reserve: reserve / 2,
+                max_fill_reserve_fraction: 100,
+                peg_multiplier: 20 * PEG_PRECISION,
+                order_step_size: 1,
+                order_tick_size: 1,
+                ..AMM::default()
+            },
+            ..PerpMarket::default()
+        };
+        let l3 = dlob.get_l3(MarketId::perp(0), oracle_price_data, 2, Some(&perp_market));
+        assert_eq!(l3.bids.len(), 3 + VAMM_LEVELS);
+        assert_eq!(l3.bids[0].maker, VAMM_MAKER);
+        assert!(l3.bids.windows(2).all(|w| w[0].price >= w[1].price));
+        assert!(l3.asks.windows(2).all(|w| w[0].price <= w[1].price));
+
+        // spot markets have no vAMM
+        let l2 = dlob.get_l2(
+            MarketId::spot(0),
+            10,
+            oracle_price_data,
+            2,
+            Some(&perp_market),
+        );
+        assert!(l2.bids.is_empty());
+    }
+
+    #[test]
+    fn test_find_nodes_to_fill() {
+        let dlob = DLOB::new();
+        let oracle_price_data = OraclePriceData {
+            price: 20 * PRICE_PRECISION_U64 as i64,
+            confidence: 1,
+            delay: 0,
+            has_sufficient_number_of_data_points: true,
+        };
+        let maker = Pubkey::new_unique();
+        let taker = Pubkey::new_unique();
+        let order = |order_id: u32,
+                     order_type: OrderType,
+                     direction: PositionDirection,
+                     price: u64| Order {
+            order_id,
+            status: OrderStatus::Open,
+            order_type,
+            market_type: MarketType::Perp,
+            direction,
+            price,
+            base_asset_amount: 1_000,
+            post_only: order_type == OrderType::Limit,
+            ..Order::default()
+        };
+        dlob.insert_order(
+            &order(
+                1,
+                OrderType::Limit,
+                PositionDirection::Short,
+                21 * PRICE_PRECISION_U64,
+            ),
+            maker,
+            1,
+        );
+        dlob.insert_order(
+            &order(2, OrderType::Market, PositionDirection::Long, 0),
+            taker,
+            1,
+        );
+        dlob.insert_order(
+            &order(3, OrderType::Market, PositionDirection::Short, 0),
+            taker,
+            1,
+        );
+
+        // no fallback liquidity, market orders without price can't be priced
+        assert!(dlob
+            .find_nodes_to_fill(MarketId::perp(0), 2, oracle_price_data, None, None)
+            .is_empty());
+
+        let nodes_to_fill = dlob.find_nodes_to_fill(
+            MarketId::perp(0),
+            2,
+            oracle_price_data,
+            Some(19 * PRICE_PRECISION_U64),
+            Some(22 * PRICE_PRECISION_U64),
+        );
+        assert_eq!(nodes_to_fill.len(), 2);
+        // taker bid crosses the resting ask
+        assert_eq!(nodes_to_fill[0].node.get_order().order_id, 2);
+        assert_eq!(nodes_to_fill[0].maker_nodes.len(), 1);
+        assert_eq!(nodes_to_fill[0].maker_nodes[0].get_order().order_id, 1);
+        // taker ask has no maker, fills against the vAMM
+        assert_eq!(nodes_to_fill[1].node.get_order().order_id, 3);
+        assert!(nodes_to_fill[1].maker_nodes.is_empty());
+    }
+
+    #[test]
+    fn test_find_nodes_to_trigger() {
+        let dlob = DLOB::new();
+        let user_account = Pubkey::new_unique();
+        let trigger_order =
+            |order_id: u32, trigger_condition: OrderTriggerCondition, trigger_price: u64| Order {
+                order_id,
+                status: OrderStatus::Open,
+                order_type: OrderType::TriggerMarket,
+                market_type: MarketType::Perp,
+                trigger_condition,
+                trigger_price,
+                base_asset_amount: 1_000,
+                ..Order::default()
+            };
+        for (order_id, condition, trigger_price) in [
+            (1, OrderTriggerCondition::Above, 25 * PRICE_PRECISION_U64),
+            (2, OrderTriggerCondition::Above, 22 * PRICE_PRECISION_U64),
+            (3, OrderTriggerCondition::Below, 15 * PRICE_PRECISION_U64),
+            (4, OrderTriggerCondition::Below, 18 * PRICE_PRECISION_U64),
+        ] {
+            dlob.insert_order(
+                &trigger_order(order_id, condition, trigger_price),
+                user_account,
+                1,
+            );
+        }
+
+        let price = |p: u64| (p * PRICE_PRECISION_U64) as i64;
+        assert!(dlob
+            .find_nodes_to_trigger(MarketId::perp(0), price(20))
+            .is_empty());
+
+        let triggered: Vec<u32> = dlob
+            .find_nodes_to_trigger(MarketId::perp(0), price(23))
+            .iter()
+            .map(|n| n.get_order().order_id)
+            .collect();
+        assert_eq!(triggered, vec![2]);
+
+        let triggered: Vec<u32> = dlob
+            .find_nodes_to_trigger(MarketId::perp(0), price(10))
+            .iter()
+            .map(|n| n.get_order().order_id)
+            .collect();
+        assert_eq!(triggered, vec![4, 3]);
+    }
 }
@@ -1,16 +1,53 @@
 use crate::{
-    dlob::dlob::DLOB, event_emitter::EventEmitter, slot_subscriber::SlotSubscriber,
-    usermap::UserMap, SdkResult,
+    dlob::{
+        dlob::{DLOBUpdate, DLOB},
+        dlob_node::DLOBNode,
+    },
+    event_emitter::EventEmitter,
+    slot_subscriber::SlotSubscriber,
+    types::MarketId,
+    usermap::{UserMap, UserUpdate},
+    SdkError, SdkResult,
 };
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use futures_util::Stream;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    account::from_account,
+    clock::{Clock, DEFAULT_MS_PER_SLOT},
+    sysvar,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    watch, Mutex,
+};
+
+/// max. buffered updates per update stream, slower consumers skip ahead
+const UPDATE_CHANNEL_SIZE: usize = 1_024;
+/// interval between samples of the on-chain clock
+const CLOCK_SYNC_INTERVAL: Duration = Duration::from_secs(10);
 
 pub struct DLOBBuilder {
     slot_subscriber: SlotSubscriber,
     usermap: UserMap,
     rebuild_frequency: u64,
-    dlob: DLOB,
+    /// the DLOB being built, shared with watchers until the next change (copy-on-write)
+    dlob: Arc<DLOB>,
     event_emitter: EventEmitter,
+    updates: broadcast::Sender<DLOBUpdate>,
+    /// the DLOB as of the last rebuild tick
+    latest: watch::Sender<Arc<DLOB>>,
+    /// last published best (bid, ask) per market
+    best_prices: Vec<(MarketId, (Option<u64>, Option<u64>))>,
+    /// (slot, unix timestamp) of the last on-chain clock sample
+    clock: Option<(u64, i64)>,
+}
+
+/// Sample the on-chain clock as (slot, unix timestamp)
+async fn sample_clock(rpc: &RpcClient) -> SdkResult<(u64, i64)> {
+    let account = rpc.get_account(&sysvar::clock::ID).await?;
+    let clock: Clock = from_account(&account).ok_or(SdkError::Deserializing)?;
+    Ok((clock.slot, clock.unix_timestamp))
 }
 
 impl DLOBBuilder {
@@ -25,42 +62,227 @@ impl DLOBBuilder {
             slot_subscriber,
             usermap,
             rebuild_frequency,
-            dlob: DLOB::new(),
+            latest: watch::channel(Arc::new(DLOB::new())).0,
+            dlob: Arc::new(DLOB::new()),
             event_emitter: EventEmitter::new(),
+            updates: broadcast::channel(UPDATE_CHANNEL_SIZE).0,
+            best_prices: Default::default(),
+            clock: None,
         }
     }
 
+    /// Build the DLOB from the usermap then keep it current by applying user account updates
+    ///
+    /// The DLOB is re-emitted every `rebuild_frequency` ms after promoting resting limit orders
     pub async fn start_building(builder: Arc<Mutex<Self>>) -> SdkResult<()> {
+        let rpc = Arc::clone(builder.lock().await.usermap.rpc());
+        let clock = sample_clock(&rpc).await?;
+
         let mut locked_builder = builder.lock().await;
         let rebuild_frequency = locked_builder.rebuild_frequency;
         locked_builder.slot_subscriber.subscribe().await?;
         locked_builder.usermap.subscribe().await?;
+
+        // queued updates are applied once the initial build completes and releases the lock
+        let update_builder = Arc::clone(&builder);
+        locked_builder
+            .usermap
+            .event_emitter
+            .subscribe(UserMap::USER_UPDATE_ID, move |event| {
+                if let Some(update) = event.as_any().downcast_ref::<UserUpdate>() {
+                    update_builder.blocking_lock().apply_user_update(update);
+                }
+            });
+        locked_builder.clock = Some(clock);
+        locked_builder.build();
         drop(locked_builder);
 
         tokio::task::spawn(async move {
             let mut timer =
                 tokio::time::interval(tokio::time::Duration::from_millis(rebuild_frequency));
+            let mut last_clock_sync = tokio::time::Instant::now();
             loop {
-                {
-                    let mut builder = builder.lock().await;
-                    builder.build();
-                }
                 let _ = timer.tick().await;
+                // sampled without holding the builder lock
+                let clock = if last_clock_sync.elapsed() >= CLOCK_SYNC_INTERVAL {
+                    match sample_clock(&rpc).await {
+                        Ok(clock) => {
+                            last_clock_sync = tokio::time::Instant::now();
+                            Some(clock)
+                        }
+                        Err(err) => {
+                            log::warn!("DLOB clock sync failed: {err:?}");
+                            None
+                        }
+                    }
+                } else {
+                    None
+                };
+                let mut builder = builder.lock().await;
+                if clock.is_some() {
+                    builder.clock = clock;
+                }
+                builder.update();
             }
         });
 
         Ok(())
     }
 
+    /// Rebuild the DLOB from scratch from the usermap
     pub fn build(&mut self) {
-        self.dlob
-            .build_from_usermap(&self.usermap, self.slot_subscriber.current_slot());
-        self.event_emitter
-            .emit(DLOBBuilder::SUBSCRIPTION_ID, Box::new(self.dlob.clone()));
+        let slot = self.slot_subscriber.current_slot();
+        let mut dlob = DLOB::new();
+        dlob.build_from_usermap(&self.usermap, slot);
+        self.dlob = Arc::new(dlob);
+        self.publish(vec![], self.dlob.markets(), slot);
+        self.emit_dlob();
+    }
+
+    /// Estimate the on-chain unix timestamp at `slot` from the last clock sample
+    fn chain_time(&self, slot: u64) -> Option<i64> {
+        let (clock_slot, clock_ts) = self.clock?;
+        let elapsed_ms = slot.saturating_sub(clock_slot) * DEFAULT_MS_PER_SLOT;
+        Some(clock_ts + (elapsed_ms / 1_000) as i64)
+    }
+
+    /// Promote resting limit orders at the current slot, prune expired orders and emit the DLOB
+    ///
+    /// Orders are only pruned once the on-chain clock has been sampled by `start_building`
+    pub fn update(&mut self) {
+        let slot = self.slot_subscriber.current_slot();
+        let now_ts = self.chain_time(slot);
+        let dlob = self.dlob_mut();
+        dlob.update_resting_limit_orders(slot);
+        let expired = match now_ts {
+            Some(now_ts) => dlob.prune_expired(now_ts),
+            None => vec![],
+        };
+        let removed = expired
+            .iter()
+            .map(|node| {
+                let order = node.get_order();
+                DLOBUpdate::OrderRemoved {
+                    market: (order.market_index, order.market_type).into(),
+                    slot,
+                    user_account: node.get_user_account(),
+                    order_id: order.order_id,
+                }
+            })
+            .collect();
+        self.publish(removed, self.dlob.markets(), slot);
+        self.emit_dlob();
+    }
+
+    /// Apply the order changes of a user account update
+    fn apply_user_update(&mut self, update: &UserUpdate) {
+        let updates = self.dlob_mut().update_user(
+            update.pubkey,
+            update.old.as_ref(),
+            &update.new,
+            update.slot,
+        );
+        let mut markets: Vec<MarketId> = vec![];
+        for market in updates.iter().map(DLOBUpdate::market) {
+            if !markets.contains(&market) {
+                markets.push(market);
+            }
+        }
+        self.publish(updates, markets, update.slot);
+    }
+
+    /// Publish `updates` along with any best bid/ask changes of `markets`
+    fn publish(&mut self, updates: Vec<DLOBUpdate>, markets: Vec<MarketId>, slot: u64) {
+        // send errors only mean there are no subscribers
+        for update in updates {
+            let _ = self.updates.send(update);
+        }
+
+        for market in markets {
+            let best = self
+                .dlob
+                .market_view(market)
+                .map(|m| m.best_fixed_prices())
+                .unwrap_or_default();
+            let idx = match self.best_prices.iter().position(|(m, _)| *m == market) {
+                Some(idx) => idx,
+                None => {
+                    self.best_prices.push((market, (None, None)));
+                    self.best_prices.len() - 1
+                }
+            };
+            let last = &mut self.best_prices[idx].1;
+            if *last != best {
+                *last = best;
+                let _ = self.updates.send(DLOBUpdate::BestBidAskChanged {
+                    market,
+                    slot,
+                    best_bid: best.0,
+                    best_ask: best.1,
+                });
+            }
+        }
+    }
+
+    /// Return the DLOB for modification, copying it first if watchers still hold it
+    fn dlob_mut(&mut self) -> &mut DLOB {
+        Arc::make_mut(&mut self.dlob)
+    }
+
+    /// Share the DLOB with watchers and send a copy to `SUBSCRIPTION_ID` subscribers
+    fn emit_dlob(&mut self) {
+        if self
+            .event_emitter
+            .has_subscribers(DLOBBuilder::SUBSCRIPTION_ID)
+        {
+            self.event_emitter.emit(
+                DLOBBuilder::SUBSCRIPTION_ID,
+                Box::new(self.dlob.as_ref().clone()),
+            );
+        }
+        // without watchers the DLOB stays unshared and is modified in place
+        if self.latest.receiver_count() > 0 {
+            self.latest.send_replace(Arc::clone(&self.dlob));
+        }
+    }
+
+    /// Stream DLOB changes, optionally only those of `market`
+    ///
+    /// Consumers falling more than `UPDATE_CHANNEL_SIZE` updates behind skip ahead
+    pub fn subscribe_updates(
+        &self,
+        market: Option<MarketId>,
+    ) -> impl Stream<Item = DLOBUpdate> + Send + 'static {
+        futures_util::stream::unfold(self.updates.subscribe(), move |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(update) if market.map_or(true, |m| update.market() == m) => {
+                        return Some((update, rx));
+                    }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("DLOB update stream lagged, skipped: {skipped}");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Return a handle to the latest DLOB
+    ///
+    /// Watchers receive an immutable view of the book every `rebuild_frequency` ms, use
+    /// `subscribe_updates` to follow individual order changes
+    pub fn watch(&self) -> watch::Receiver<Arc<DLOB>> {
+        // not published while there were no watchers
+        if !Arc::ptr_eq(&self.latest.borrow(), &self.dlob) {
+            self.latest.send_replace(Arc::clone(&self.dlob));
+        }
+        self.latest.subscribe()
     }
 
     pub fn get_dlob(&self) -> DLOB {
-        self.dlob.clone()
+        self.dlob.as_ref().clone()
     }
 }
 
@@ -69,9 +291,123 @@ mod tests {
     use super::*;
     use crate::memcmp::get_user_with_order_filter;
     use crate::utils::get_ws_url;
+    use drift::state::user::{MarketType, Order, OrderStatus, OrderType, User};
     use env_logger;
+    use futures_util::StreamExt;
     use solana_sdk::commitment_config::CommitmentConfig;
     use solana_sdk::commitment_config::CommitmentLevel;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[tokio::test]
+    async fn dlob_update_stream_and_watch() {
+        let usermap = UserMap::new(
+            CommitmentConfig::confirmed(),
+            "http://localhost:8899".to_string(),
+            false,
+            None,
+        );
+        let slot_subscriber = SlotSubscriber::new("ws://localhost:8900".to_string());
+        let mut builder = DLOBBuilder::new(slot_subscriber, usermap, 100);
+        let mut updates = Box::pin(builder.subscribe_updates(Some(MarketId::perp(0))));
+        let mut latest = builder.watch();
+
+        let pubkey = Pubkey::new_unique();
+        let mut user = User::default();
+        for (idx, market_type) in [MarketType::Perp, MarketType::Spot].into_iter().enumerate() {
+            user.orders[idx] = Order {
+                order_id: idx as u32 + 1,
+                status: OrderStatus::Open,
+                order_type: OrderType::Limit,
+                market_type,
+                price: 10,
+                base_asset_amount: 1,
+                post_only: true,
+                ..Order::default()
+            };
+        }
+        builder.apply_user_update(&UserUpdate {
+            pubkey,
+            old: None,
+            new: user,
+            slot: 1,
+        });
+
+        // watchers are only notified on the rebuild tick
+        assert!(!latest.has_changed().unwrap());
+        builder.update();
+        assert!(latest.has_changed().unwrap());
+        let dlob = Arc::clone(&latest.borrow_and_update());
+        assert_eq!(dlob.size(), (1, 1));
+        // shared with watchers rather than copied
+        assert!(Arc::ptr_eq(&dlob, &builder.dlob));
+
+        // spot updates are filtered out
+        assert_eq!(
+            updates.next().await,
+            Some(DLOBUpdate::OrderAdded {
+                market: MarketId::perp(0),
+                slot: 1,
+                user_account: pubkey,
+                order: user.orders[0],
+            })
+        );
+        assert_eq!(
+            updates.next().await,
+            Some(DLOBUpdate::BestBidAskChanged {
+                market: MarketId::perp(0),
+                slot: 1,
+                best_bid: Some(10),
+                best_ask: None,
+            })
+        );
+
+        builder.apply_user_update(&UserUpdate {
+            pubkey,
+            old: Some(user),
+            new: User::default(),
+            slot: 2,
+        });
+        assert_eq!(
+            updates.next().await,
+            Some(DLOBUpdate::OrderRemoved {
+                market: MarketId::perp(0),
+                slot: 2,
+                user_account: pubkey,
+                order_id: 1,
+            })
+        );
+        assert_eq!(
+            updates.next().await,
+            Some(DLOBUpdate::BestBidAskChanged {
+                market: MarketId::perp(0),
+                slot: 2,
+                best_bid: None,
+                best_ask: None,
+            })
+        );
+        // the watched copy is not mutated by later updates
+        assert_eq!(dlob.size(), (1, 1));
+        builder.update();
+        assert!(latest.has_changed().unwrap());
+        assert_eq!(latest.borrow().size(), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn chain_time_from_clock_sample() {
+        let usermap = UserMap::new(
+            CommitmentConfig::confirmed(),
+            "http://localhost:8899".to_string(),
+            false,
+            None,
+        );
+        let slot_subscriber = SlotSubscriber::new("ws://localhost:8900".to_string());
+        let mut builder = DLOBBuilder::new(slot_subscriber, usermap, 100);
+        assert!(builder.chain_time(110).is_none());
+
+        builder.clock = Some((100, 1_700_000_000));
+        assert_eq!(builder.chain_time(110), Some(1_700_000_004));
+        assert_eq!(builder.chain_time(90), Some(1_700_000_000));
+    }
 
     #[tokio::test]
     #[cfg(rpc_tests)]
@@ -1,8 +1,9 @@
 use crate::math::order::get_limit_price;
 use drift::state::{oracle::OraclePriceData, user::Order};
+use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum NodeType {
     TakingLimit,
     RestingLimit,
@@ -36,55 +37,6 @@ pub enum Node {
     VAMMNode(VAMMNode),
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct DirectionalNode {
-    pub node: Node,
-    sort_direction: SortDirection,
-}
-
-impl DirectionalNode {
-    pub fn new(node: Node, sort_direction: SortDirection) -> Self {
-        Self {
-            node,
-            sort_direction,
-        }
-    }
-}
-
-impl PartialEq for DirectionalNode {
-    fn eq(&self, other: &Self) -> bool {
-        self.node.eq(&other.node)
-    }
-}
-
-impl Eq for DirectionalNode {}
-
-impl PartialOrd for DirectionalNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
-}
-
-impl Ord for DirectionalNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let mut cmp = self
-            .node
-            .get_sort_value(self.node.get_order())
-            .partial_cmp(&other.node.get_sort_value(other.node.get_order()))
-            .unwrap_or(std::cmp::Ordering::Equal);
-
-        if cmp == std::cmp::Ordering::Equal {
-            cmp = self.node.get_order().slot.cmp(&other.node.get_order().slot);
-        }
-
-        if self.sort_direction == SortDirection::Ascending {
-            cmp = cmp.reverse();
-        }
-
-        cmp
-    }
-}
-
 impl PartialEq for Node {
     fn eq(&self, other: &Self) -> bool {
         self.get_sort_value(self.get_order()) == other.get_sort_value(other.get_order())
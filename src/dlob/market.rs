@@ -1,8 +1,9 @@
 use dashmap::{DashMap, DashSet};
 use drift::controller::position::PositionDirection;
 use drift::state::user::{Order, OrderTriggerCondition, OrderType};
+use solana_sdk::pubkey::Pubkey;
 
-use crate::dlob::dlob_node::{NodeType, SortDirection};
+use crate::dlob::dlob_node::{Node, NodeType, SortDirection};
 use crate::dlob::order_list::Orderlist;
 use crate::is_one_of_variant;
 use crate::math::order::is_resting_limit_order;
@@ -52,7 +53,7 @@ impl Market {
         }
     }
 
-    pub(crate) fn get_order_list_for_node_type(&self, node_type: NodeType) -> Orderlist {
+    pub(crate) fn get_order_list_for_node_type(&self, node_type: NodeType) -> &Orderlist {
         match node_type {
             NodeType::RestingLimit => &self.resting_limit_orders,
             NodeType::FloatingLimit => &self.floating_limit_orders,
@@ -61,16 +62,57 @@ impl Market {
             NodeType::Trigger => &self.trigger_orders,
             NodeType::VAMM => panic!("VAMM order list not found"),
         }
-        .clone()
+    }
+
+    fn order_lists(&self) -> [&Orderlist; 5] {
+        [
+            &self.resting_limit_orders,
+            &self.floating_limit_orders,
+            &self.taking_limit_orders,
+            &self.market_orders,
+            &self.trigger_orders,
+        ]
+    }
+
+    /// Return the node of order signature `order_sig`, if it exists in any list
+    pub(crate) fn get_node(&self, order_sig: &String) -> Option<Node> {
+        self.order_lists()
+            .into_iter()
+            .find_map(|order_list| order_list.get_node(order_sig))
+    }
+
+    /// Iterate all nodes of the market
+    pub(crate) fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.order_lists()
+            .into_iter()
+            .flat_map(|order_list| order_list.bids().chain(order_list.asks()))
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.order_lists()
+            .iter()
+            .map(|order_list| order_list.size())
+            .sum()
+    }
+
+    /// Remove the order `order_id` of `user_account` from whichever list holds it
+    pub(crate) fn remove_order(&mut self, order_id: u32, user_account: Pubkey) -> Option<Node> {
+        [
+            &mut self.resting_limit_orders,
+            &mut self.floating_limit_orders,
+            &mut self.taking_limit_orders,
+            &mut self.market_orders,
+            &mut self.trigger_orders,
+        ]
+        .into_iter()
+        .find_map(|order_list| order_list.remove(order_id, user_account))
     }
 
     /// for debugging
     pub fn print_all_orders(&self) {
-        self.resting_limit_orders.print();
-        self.floating_limit_orders.print();
-        self.taking_limit_orders.print();
-        self.market_orders.print();
-        self.trigger_orders.print();
+        for order_list in self.order_lists() {
+            order_list.print();
+        }
     }
 }
 
@@ -127,7 +169,7 @@ impl Exchange {
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &DashMap<u16, Market>> {
-        vec![&self.perp, &self.spot].into_iter()
+        [&self.perp, &self.spot].into_iter()
     }
 
     pub fn clear(&self) {
@@ -157,63 +199,29 @@ impl Exchange {
         };
     }
 
-    pub fn get_order_lists(&self) -> Vec<Orderlist> {
-        let mut order_lists = vec![];
-
-        for market_type_ref in self.iter() {
-            for market_ref in market_type_ref.iter() {
-                let market = market_ref.value();
-                order_lists.push(market.resting_limit_orders.clone());
-                order_lists.push(market.floating_limit_orders.clone());
-                order_lists.push(market.taking_limit_orders.clone());
-                order_lists.push(market.market_orders.clone());
-                order_lists.push(market.trigger_orders.clone());
-            }
-        }
-
-        order_lists
+    /// Return the node of order signature `order_sig`, if it exists in any market
+    pub fn get_node(&self, order_sig: &String) -> Option<Node> {
+        self.iter().find_map(|markets| {
+            markets
+                .iter()
+                .find_map(|market| market.value().get_node(order_sig))
+        })
     }
 
     /// for debugging
     #[allow(dead_code)]
     pub fn size(&self) -> usize {
-        let mut size = 0;
-        for market_type_ref in self.iter() {
-            for market in market_type_ref.iter() {
-                size += market.value().resting_limit_orders.size();
-                size += market.value().floating_limit_orders.size();
-                size += market.value().taking_limit_orders.size();
-                size += market.value().market_orders.size();
-                size += market.value().trigger_orders.size();
-            }
-        }
-        size
+        self.perp_size() + self.spot_size()
     }
 
     /// for debugging
     pub fn perp_size(&self) -> usize {
-        let mut size = 0;
-        for market in self.perp.iter() {
-            size += market.value().resting_limit_orders.size();
-            size += market.value().floating_limit_orders.size();
-            size += market.value().taking_limit_orders.size();
-            size += market.value().market_orders.size();
-            size += market.value().trigger_orders.size();
-        }
-        size
+        self.perp.iter().map(|market| market.value().size()).sum()
     }
 
     /// for debugging
     pub fn spot_size(&self) -> usize {
-        let mut size = 0;
-        for market in self.spot.iter() {
-            size += market.value().resting_limit_orders.size();
-            size += market.value().floating_limit_orders.size();
-            size += market.value().taking_limit_orders.size();
-            size += market.value().market_orders.size();
-            size += market.value().trigger_orders.size();
-        }
-        size
+        self.spot.iter().map(|market| market.value().size()).sum()
     }
 }
 
@@ -3,3 +3,4 @@ pub mod dlob_builder;
 pub mod dlob_node;
 mod market;
 mod order_list;
+pub mod snapshot;
@@ -1,14 +1,54 @@
-use std::collections::BinaryHeap;
+use std::collections::{BTreeMap, HashMap};
+use std::iter::Peekable;
 
-use dashmap::DashMap;
+use drift::state::oracle::OraclePriceData;
+use solana_sdk::pubkey::Pubkey;
 
-use crate::dlob::dlob_node::{get_order_signature, DLOBNode, DirectionalNode, Node, SortDirection};
+use crate::dlob::dlob_node::{get_order_signature, DLOBNode, Node, SortDirection};
 
+/// Position of a node within one side of an `Orderlist`
+///
+/// Ordered best first: by sort value in the side's direction, then oldest slot first.
+/// order id and user make the key unique so no two nodes ever compare equal.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct NodeKey {
+    sort_value: i128,
+    slot: u64,
+    order_id: u32,
+    user_account: Pubkey,
+}
+
+impl NodeKey {
+    fn new(node: &Node, sort_direction: SortDirection) -> Self {
+        let order = node.get_order();
+        let sort_value = node.get_sort_value(order).unwrap_or_default();
+        Self {
+            sort_value: match sort_direction {
+                SortDirection::Ascending => sort_value,
+                SortDirection::Descending => -sort_value,
+            },
+            slot: order.slot,
+            order_id: order.order_id,
+            user_account: node.get_user_account(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Side {
+    Bid,
+    Ask,
+}
+
+/// Bids and asks of one order type, kept sorted best first
+///
+/// Sides can be iterated in order any number of times without cloning or allocating
 #[derive(Clone, Debug)]
 pub struct Orderlist {
-    pub bids: BinaryHeap<DirectionalNode>,
-    pub asks: BinaryHeap<DirectionalNode>,
-    pub order_sigs: DashMap<String, Node>,
+    bids: BTreeMap<NodeKey, Node>,
+    asks: BTreeMap<NodeKey, Node>,
+    /// order signature to its position in the list
+    order_sigs: HashMap<String, (Side, NodeKey)>,
     bid_sort_direction: SortDirection,
     ask_sort_direction: SortDirection,
 }
@@ -16,9 +56,9 @@ pub struct Orderlist {
 impl Orderlist {
     pub fn new(bid_sort_direction: SortDirection, ask_sort_direction: SortDirection) -> Self {
         Orderlist {
-            bids: BinaryHeap::new(),
-            asks: BinaryHeap::new(),
-            order_sigs: DashMap::new(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            order_sigs: HashMap::new(),
             bid_sort_direction,
             ask_sort_direction,
         }
@@ -26,48 +66,100 @@ impl Orderlist {
 
     /// for debugging
     pub fn print(&self) {
-        println!("Bids: {:?}", self.bids);
-        println!("Asks: {:?}", self.asks);
+        println!("Bids: {:?}", self.bids.values().collect::<Vec<_>>());
+        println!("Asks: {:?}", self.asks.values().collect::<Vec<_>>());
     }
 
+    /// Insert a bid, replacing any existing version of the order
     pub fn insert_bid(&mut self, node: Node) {
-        let order_sig = get_order_signature(node.get_order().order_id, node.get_user_account());
-        self.order_sigs.insert(order_sig.clone(), node);
-        let directional = DirectionalNode::new(node, self.bid_sort_direction);
-        self.bids.push(directional);
+        self.insert(Side::Bid, node);
     }
 
+    /// Insert an ask, replacing any existing version of the order
     pub fn insert_ask(&mut self, node: Node) {
+        self.insert(Side::Ask, node);
+    }
+
+    fn insert(&mut self, side: Side, node: Node) {
+        self.remove(node.get_order().order_id, node.get_user_account());
         let order_sig = get_order_signature(node.get_order().order_id, node.get_user_account());
-        self.order_sigs.insert(order_sig.clone(), node);
-        let directional = DirectionalNode::new(node, self.ask_sort_direction);
-        self.asks.push(directional);
+        let key = match side {
+            Side::Bid => NodeKey::new(&node, self.bid_sort_direction),
+            Side::Ask => NodeKey::new(&node, self.ask_sort_direction),
+        };
+        self.order_sigs.insert(order_sig, (side, key));
+        match side {
+            Side::Bid => self.bids.insert(key, node),
+            Side::Ask => self.asks.insert(key, node),
+        };
     }
 
-    pub fn get_best_bid(&mut self) -> Option<Node> {
-        if let Some(node) = self.bids.pop().map(|node| node.node) {
-            let order_sig = get_order_signature(node.get_order().order_id, node.get_user_account());
-            if self.order_sigs.contains_key(&order_sig) {
-                self.order_sigs.remove(&order_sig);
-                return Some(node);
-            }
+    /// Iterate the bids best first
+    pub fn bids(&self) -> impl DoubleEndedIterator<Item = &Node> + ExactSizeIterator + '_ {
+        self.bids.values()
+    }
+
+    /// Iterate the asks best first
+    pub fn asks(&self) -> impl DoubleEndedIterator<Item = &Node> + ExactSizeIterator + '_ {
+        self.asks.values()
+    }
+
+    pub fn get_best_bid(&self) -> Option<&Node> {
+        self.bids.values().next()
+    }
+
+    pub fn get_best_ask(&self) -> Option<&Node> {
+        self.asks.values().next()
+    }
+
+    /// Remove the order `order_id` of `user_account` from the list
+    ///
+    /// Returns the removed node if it existed
+    pub fn remove(&mut self, order_id: u32, user_account: Pubkey) -> Option<Node> {
+        let order_sig = get_order_signature(order_id, user_account);
+        let (side, key) = self.order_sigs.remove(&order_sig)?;
+        match side {
+            Side::Bid => self.bids.remove(&key),
+            Side::Ask => self.asks.remove(&key),
         }
-        None
     }
 
-    pub fn get_best_ask(&mut self) -> Option<Node> {
-        if let Some(node) = self.asks.pop().map(|node| node.node) {
-            let order_sig = get_order_signature(node.get_order().order_id, node.get_user_account());
-            if self.order_sigs.contains_key(&order_sig) {
-                self.order_sigs.remove(&order_sig);
-                return Some(node);
-            }
+    /// Remove all nodes matching `f`
+    ///
+    /// Returns the removed (bids, asks)
+    pub fn remove_where(&mut self, mut f: impl FnMut(&Node) -> bool) -> (Vec<Node>, Vec<Node>) {
+        let mut removed_bids = vec![];
+        let mut removed_asks = vec![];
+        for (nodes, removed) in [
+            (&mut self.bids, &mut removed_bids),
+            (&mut self.asks, &mut removed_asks),
+        ] {
+            nodes.retain(|_, node| {
+                if f(node) {
+                    removed.push(*node);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        for node in removed_bids.iter().chain(removed_asks.iter()) {
+            self.order_sigs.remove(&get_order_signature(
+                node.get_order().order_id,
+                node.get_user_account(),
+            ));
         }
-        None
+
+        (removed_bids, removed_asks)
     }
 
     pub fn get_node(&self, order_sig: &String) -> Option<Node> {
-        self.order_sigs.get(order_sig).map(|node| *node)
+        let (side, key) = self.order_sigs.get(order_sig)?;
+        match side {
+            Side::Bid => self.bids.get(key),
+            Side::Ask => self.asks.get(key),
+        }
+        .copied()
     }
 
     pub fn bids_empty(&self) -> bool {
@@ -83,13 +175,78 @@ impl Orderlist {
     }
 }
 
+/// Merges two price sorted node iterators into one sorted by price at a given oracle price
+///
+/// `a` goes first at equal prices
+pub(crate) struct MergeByPrice<'a, A, B>
+where
+    A: Iterator<Item = &'a Node>,
+    B: Iterator<Item = &'a Node>,
+{
+    a: Peekable<A>,
+    b: Peekable<B>,
+    oracle_price_data: OraclePriceData,
+    slot: u64,
+    sort_direction: SortDirection,
+}
+
+impl<'a, A, B> MergeByPrice<'a, A, B>
+where
+    A: Iterator<Item = &'a Node>,
+    B: Iterator<Item = &'a Node>,
+{
+    pub(crate) fn new(
+        a: A,
+        b: B,
+        oracle_price_data: OraclePriceData,
+        slot: u64,
+        sort_direction: SortDirection,
+    ) -> Self {
+        Self {
+            a: a.peekable(),
+            b: b.peekable(),
+            oracle_price_data,
+            slot,
+            sort_direction,
+        }
+    }
+}
+
+impl<'a, A, B> Iterator for MergeByPrice<'a, A, B>
+where
+    A: Iterator<Item = &'a Node>,
+    B: Iterator<Item = &'a Node>,
+{
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let take_a = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => {
+                let price_a = a.get_price(self.oracle_price_data, self.slot);
+                let price_b = b.get_price(self.oracle_price_data, self.slot);
+                match self.sort_direction {
+                    SortDirection::Ascending => price_a <= price_b,
+                    SortDirection::Descending => price_a >= price_b,
+                }
+            }
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if take_a {
+            self.a.next()
+        } else {
+            self.b.next()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::dlob::dlob_node::{create_node, NodeType};
 
     use super::*;
     use drift::state::user::Order;
-    use solana_sdk::pubkey::Pubkey;
 
     #[test]
     fn test_insertion_and_ordering() {
@@ -170,16 +327,128 @@ mod tests {
         orderlist.insert_ask(node_9);
         orderlist.insert_ask(node_10);
 
+        let slots = |nodes: &mut dyn Iterator<Item = &Node>| {
+            nodes.map(|n| n.get_order().slot).collect::<Vec<u64>>()
+        };
+        assert_eq!(slots(&mut orderlist.bids()), vec![1, 2, 3, 4, 5]);
+        assert_eq!(slots(&mut orderlist.asks()), vec![1, 2, 3, 4, 5]);
+        // iteration is non-destructive
         assert_eq!(orderlist.get_best_bid().unwrap().get_order().slot, 1);
-        assert_eq!(orderlist.get_best_bid().unwrap().get_order().slot, 2);
-        assert_eq!(orderlist.get_best_bid().unwrap().get_order().slot, 3);
-        assert_eq!(orderlist.get_best_bid().unwrap().get_order().slot, 4);
-        assert_eq!(orderlist.get_best_bid().unwrap().get_order().slot, 5);
-
-        assert_eq!(orderlist.get_best_ask().unwrap().get_order().slot, 1);
-        assert_eq!(orderlist.get_best_ask().unwrap().get_order().slot, 2);
-        assert_eq!(orderlist.get_best_ask().unwrap().get_order().slot, 3);
-        assert_eq!(orderlist.get_best_ask().unwrap().get_order().slot, 4);
-        assert_eq!(orderlist.get_best_ask().unwrap().get_order().slot, 5);
+        assert_eq!(orderlist.get_best_bid().unwrap().get_order().slot, 1);
+        assert_eq!(orderlist.size(), 10);
+    }
+
+    #[test]
+    fn test_price_time_priority() {
+        let mut orderlist = Orderlist::new(SortDirection::Descending, SortDirection::Ascending);
+        let user_account = Pubkey::new_unique();
+        let order = |order_id: u32, price: u64, slot: u64| Order {
+            order_id,
+            price,
+            slot,
+            ..Order::default()
+        };
+        // equal price and slot orders are all kept
+        for (order_id, price, slot) in [(1, 10, 2), (2, 12, 3), (3, 10, 1), (4, 10, 1)] {
+            let node = create_node(
+                NodeType::RestingLimit,
+                order(order_id, price, slot),
+                user_account,
+            );
+            orderlist.insert_bid(node);
+            orderlist.insert_ask(create_node(
+                NodeType::RestingLimit,
+                order(order_id, price, slot),
+                Pubkey::new_unique(),
+            ));
+        }
+
+        let ids = |nodes: &mut dyn Iterator<Item = &Node>| {
+            nodes.map(|n| n.get_order().order_id).collect::<Vec<u32>>()
+        };
+        assert_eq!(ids(&mut orderlist.bids()), vec![2, 3, 4, 1]);
+        assert_eq!(ids(&mut orderlist.asks()), vec![3, 4, 1, 2]);
+
+        // re-inserting replaces the existing order
+        orderlist.insert_bid(create_node(
+            NodeType::RestingLimit,
+            order(2, 9, 3),
+            user_account,
+        ));
+        assert_eq!(ids(&mut orderlist.bids()), vec![3, 4, 1, 2]);
+        assert_eq!(orderlist.size(), 8);
+
+        let (bids, asks) = orderlist.remove_where(|n| n.get_order().price == 10);
+        assert_eq!((bids.len(), asks.len()), (3, 3));
+        assert_eq!(ids(&mut orderlist.bids()), vec![2]);
+        assert!(orderlist
+            .get_node(&get_order_signature(3, user_account))
+            .is_none());
+    }
+
+    #[test]
+    fn test_merge_by_price() {
+        let user_account = Pubkey::new_unique();
+        let oracle_price_data = OraclePriceData {
+            price: 100,
+            confidence: 1,
+            delay: 0,
+            has_sufficient_number_of_data_points: true,
+        };
+        let resting: Vec<Node> = [(1, 95), (2, 99), (3, 103)]
+            .into_iter()
+            .map(|(order_id, price)| {
+                let order = Order {
+                    order_id,
+                    price,
+                    ..Order::default()
+                };
+                create_node(NodeType::RestingLimit, order, user_account)
+            })
+            .collect();
+        let floating: Vec<Node> = [(4, -1), (5, 3)]
+            .into_iter()
+            .map(|(order_id, oracle_price_offset)| {
+                let order = Order {
+                    order_id,
+                    oracle_price_offset,
+                    ..Order::default()
+                };
+                create_node(NodeType::FloatingLimit, order, user_account)
+            })
+            .collect();
+
+        let merged: Vec<u32> = MergeByPrice::new(
+            resting.iter(),
+            floating.iter(),
+            oracle_price_data,
+            0,
+            SortDirection::Ascending,
+        )
+        .map(|n| n.get_order().order_id)
+        .collect();
+        assert_eq!(merged, vec![1, 2, 4, 3, 5]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut orderlist = Orderlist::new(SortDirection::Ascending, SortDirection::Ascending);
+        let user_account = Pubkey::new_unique();
+        for order_id in 1..=3 {
+            let order = Order {
+                order_id,
+                slot: order_id as u64,
+                ..Order::default()
+            };
+            orderlist.insert_bid(create_node(NodeType::TakingLimit, order, user_account));
+        }
+
+        assert!(orderlist.remove(2, user_account).is_some());
+        assert!(orderlist.remove(2, user_account).is_none());
+        assert!(orderlist.remove(1, Pubkey::new_unique()).is_none());
+        assert_eq!(orderlist.size(), 2);
+
+        let order_ids: Vec<u32> = orderlist.bids().map(|n| n.get_order().order_id).collect();
+        assert_eq!(order_ids, vec![1, 3]);
     }
 }
@@ -0,0 +1,351 @@
+//! Point-in-time snapshots of the `DLOB`
+//!
+//! Snapshots can be encoded as compact binary or JSON, see `DLOB::snapshot` and `DLOB::from_snapshot`
+use std::path::Path;
+
+use anchor_lang::{AnchorDeserialize, AnchorSerialize};
+use drift::state::user::Order;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    dlob::dlob_node::NodeType,
+    serde_helpers::serde_order,
+    types::{SdkError, SdkResult},
+};
+
+/// binary snapshot magic bytes
+const MAGIC: &[u8; 4] = b"DLOB";
+/// binary snapshot format version
+const VERSION: u8 = 2;
+/// magic + version + slot + node count
+const HEADER_LEN: usize = 4 + 1 + 8 + 4;
+
+/// An order captured in a `DLOBSnapshot`
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "JsonNode", try_from = "JsonNode")]
+pub struct SnapshotNode {
+    pub node_type: NodeType,
+    pub user_account: Pubkey,
+    pub order: Order,
+}
+
+/// All orders of a `DLOB` at a slot
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DLOBSnapshot {
+    pub slot: u64,
+    pub nodes: Vec<SnapshotNode>,
+}
+
+impl DLOBSnapshot {
+    /// Encode the snapshot as compact binary
+    ///
+    /// layout (little-endian): `"DLOB" | version: u8 | slot: u64 | count: u32 | count * (node type: u8 | user: [u8; 32] | order: borsh)`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf =
+            Vec::with_capacity(HEADER_LEN + self.nodes.len() * (33 + std::mem::size_of::<Order>()));
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        buf.extend_from_slice(&self.slot.to_le_bytes());
+        buf.extend_from_slice(&(self.nodes.len() as u32).to_le_bytes());
+        for node in self.nodes.iter() {
+            buf.push(node_type_to_u8(node.node_type));
+            buf.extend_from_slice(node.user_account.as_ref());
+            node.order.serialize(&mut buf).expect("serializes");
+        }
+
+        buf
+    }
+
+    /// Decode a snapshot from its binary encoding
+    pub fn from_bytes(data: &[u8]) -> SdkResult<Self> {
+        let header = data.get(..HEADER_LEN).ok_or(SdkError::Deserializing)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(SdkError::Deserializing);
+        }
+        let slot = u64::from_le_bytes(header[5..13].try_into().expect("8 bytes"));
+        let count = u32::from_le_bytes(header[13..17].try_into().expect("4 bytes")) as usize;
+
+        let mut body = &data[HEADER_LEN..];
+        // every node takes at least its type and user account
+        if count > body.len() / 33 {
+            return Err(SdkError::Deserializing);
+        }
+        let mut nodes = Vec::with_capacity(count);
+        for _ in 0..count {
+            let prefix = body.get(..33).ok_or(SdkError::Deserializing)?;
+            body = &body[33..];
+            nodes.push(SnapshotNode {
+                node_type: node_type_from_u8(prefix[0])?,
+                user_account: Pubkey::new_from_array(prefix[1..].try_into().expect("32 bytes")),
+                order: Order::deserialize(&mut body).map_err(|_| SdkError::Deserializing)?,
+            });
+        }
+        if !body.is_empty() {
+            return Err(SdkError::Deserializing);
+        }
+
+        Ok(Self { slot, nodes })
+    }
+
+    /// Encode the snapshot as JSON
+    pub fn to_json(&self) -> SdkResult<String> {
+        serde_json::to_string(self).map_err(|err| std::io::Error::from(err).into())
+    }
+
+    /// Decode a snapshot from JSON
+    pub fn from_json(data: &str) -> SdkResult<Self> {
+        serde_json::from_str(data).map_err(|_| SdkError::Deserializing)
+    }
+
+    /// Write the snapshot to `path` in binary
+    pub fn save(&self, path: impl AsRef<Path>) -> SdkResult<()> {
+        std::fs::write(path, self.to_bytes()).map_err(Into::into)
+    }
+
+    /// Read a binary snapshot from `path`
+    pub fn load(path: impl AsRef<Path>) -> SdkResult<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Write the snapshot to `path` as JSON
+    pub fn save_json(&self, path: impl AsRef<Path>) -> SdkResult<()> {
+        std::fs::write(path, self.to_json()?).map_err(Into::into)
+    }
+
+    /// Read a JSON snapshot from `path`
+    pub fn load_json(path: impl AsRef<Path>) -> SdkResult<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+}
+
+/// JSON representation of a `SnapshotNode`
+#[derive(Serialize, Deserialize)]
+struct JsonNode {
+    node_type: NodeType,
+    /// base58 user account
+    user_account: String,
+    #[serde(with = "serde_order")]
+    order: Order,
+}
+
+impl From<SnapshotNode> for JsonNode {
+    fn from(value: SnapshotNode) -> Self {
+        Self {
+            node_type: value.node_type,
+            user_account: value.user_account.to_string(),
+            order: value.order,
+        }
+    }
+}
+
+impl TryFrom<JsonNode> for SnapshotNode {
+    type Error = SdkError;
+    fn try_from(value: JsonNode) -> Result<Self, Self::Error> {
+        Ok(Self {
+            node_type: value.node_type,
+            user_account: value
+                .user_account
+                .parse()
+                .map_err(|_| SdkError::InvalidBase58)?,
+            order: value.order,
+        })
+    }
+}
+
+fn node_type_to_u8(node_type: NodeType) -> u8 {
+    match node_type {
+        NodeType::TakingLimit => 0,
+        NodeType::RestingLimit => 1,
+        NodeType::FloatingLimit => 2,
+        NodeType::Market => 3,
+        NodeType::Trigger => 4,
+        NodeType::VAMM => 5,
+    }
+}
+
+fn node_type_from_u8(value: u8) -> SdkResult<NodeType> {
+    match value {
+        0 => Ok(NodeType::TakingLimit),
+        1 => Ok(NodeType::RestingLimit),
+        2 => Ok(NodeType::FloatingLimit),
+        3 => Ok(NodeType::Market),
+        4 => Ok(NodeType::Trigger),
+        // vAMM nodes are not stored in the DLOB
+        _ => Err(SdkError::Deserializing),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use drift::{
+        controller::position::PositionDirection,
+        math::constants::PRICE_PRECISION_U64,
+        state::user::{MarketType, OrderStatus, OrderTriggerCondition, OrderType},
+    };
+
+    use super::*;
+    use crate::dlob::{dlob::DLOB, dlob_node::DLOBNode, market::SubType};
+
+    fn test_dlob() -> (DLOB, Pubkey) {
+        let dlob = DLOB::new();
+        let user_account = Pubkey::new_unique();
+        let order = |order_id: u32, market_type: MarketType| Order {
+            order_id,
+            status: OrderStatus::Open,
+            order_type: OrderType::Limit,
+            market_type,
+            market_index: 1,
+            direction: PositionDirection::Short,
+            price: order_id as u64 * PRICE_PRECISION_U64,
+            base_asset_amount: 1_000,
+            slot: 1,
+            auction_duration: 10,
+            ..Order::default()
+        };
+        // taking limit orders
+        dlob.insert_order(&order(1, MarketType::Perp), user_account, 1);
+        dlob.insert_order(&order(2, MarketType::Spot), user_account, 1);
+        // resting limit, floating and trigger orders
+        dlob.insert_order(
+            &Order {
+                post_only: true,
+                ..order(3, MarketType::Perp)
+            },
+            user_account,
+            1,
+        );
+        dlob.insert_order(
+            &Order {
+                oracle_price_offset: 100,
+                ..order(4, MarketType::Perp)
+            },
+            user_account,
+            1,
+        );
+        dlob.insert_order(
+            &Order {
+                order_type: OrderType::TriggerLimit,
+                trigger_condition: OrderTriggerCondition::Above,
+                trigger_price: 5 * PRICE_PRECISION_U64,
+                ..order(5, MarketType::Perp)
+            },
+            user_account,
+            1,
+        );
+
+        (dlob, user_account)
+    }
+
+    fn assert_restored(dlob: &DLOB, restored: &DLOB, user_account: Pubkey) {
+        assert_eq!(restored.size(), dlob.size());
+        for order_id in 1..=5 {
+            assert_eq!(
+                restored.get_order(order_id, user_account),
+                dlob.get_order(order_id, user_account)
+            );
+        }
+        for node_type in [
+            NodeType::TakingLimit,
+            NodeType::RestingLimit,
+            NodeType::FloatingLimit,
+            NodeType::Trigger,
+        ] {
+            for sub_type in [SubType::Bid, SubType::Ask] {
+                let ids = |dlob: &DLOB| {
+                    dlob.get_best_orders(MarketType::Perp, sub_type, node_type, 1)
+                        .iter()
+                        .map(|n| (n.get_order().order_id, n.get_node_type()))
+                        .collect::<Vec<_>>()
+                };
+                assert_eq!(ids(restored), ids(dlob));
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot_binary_round_trip() {
+        let (dlob, user_account) = test_dlob();
+        let snapshot = dlob.snapshot(2);
+        assert_eq!(snapshot.nodes.len(), 5);
+
+        let bytes = snapshot.to_bytes();
+        let order_len = Order::default().try_to_vec().unwrap().len();
+        assert_eq!(bytes.len(), HEADER_LEN + 5 * (33 + order_len));
+        let decoded = DLOBSnapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, snapshot);
+        assert_restored(&dlob, &DLOB::from_snapshot(&decoded), user_account);
+
+        assert!(DLOBSnapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(DLOBSnapshot::from_bytes(b"DLOX").is_err());
+
+        let path = std::env::temp_dir().join(format!("dlob-{}.bin", Pubkey::new_unique()));
+        snapshot.save(&path).unwrap();
+        assert_eq!(DLOBSnapshot::load(&path).unwrap(), snapshot);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn snapshot_rejects_invalid_order() {
+        // locate the order status byte
+        let open = Order {
+            status: OrderStatus::Open,
+            ..Order::default()
+        };
+        let open = open.try_to_vec().unwrap();
+        let init = Order::default().try_to_vec().unwrap();
+        let status_idx = open
+            .iter()
+            .zip(init.iter())
+            .position(|(a, b)| a != b)
+            .unwrap();
+
+        let snapshot = DLOBSnapshot {
+            slot: 1,
+            nodes: vec![SnapshotNode {
+                node_type: NodeType::RestingLimit,
+                user_account: Pubkey::new_unique(),
+                order: Order::default(),
+            }],
+        };
+        let mut bytes = snapshot.to_bytes();
+        bytes[HEADER_LEN + 33 + status_idx] = u8::MAX;
+        assert!(DLOBSnapshot::from_bytes(&bytes).is_err());
+
+        let json = snapshot
+            .to_json()
+            .unwrap()
+            .replace(r#""status":"Init""#, r#""status":"Closed""#);
+        assert!(DLOBSnapshot::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn from_snapshot_keeps_node_types() {
+        let (dlob, user_account) = test_dlob();
+        // auctions end at slot 11
+        let restored = DLOB::from_snapshot(&dlob.snapshot(20));
+        // orders are not re-classified by an update at or before the snapshot slot
+        restored.update_resting_limit_orders(20);
+        assert_restored(&dlob, &restored, user_account);
+    }
+
+    #[test]
+    fn snapshot_json_round_trip() {
+        let (dlob, user_account) = test_dlob();
+        let snapshot = dlob.snapshot(2);
+
+        let json = snapshot.to_json().unwrap();
+        // orders are stored by field
+        assert!(json.contains(r#""status":"Open""#));
+        let decoded = DLOBSnapshot::from_json(&json).unwrap();
+        assert_eq!(decoded, snapshot);
+        assert_restored(&dlob, &DLOB::from_snapshot(&decoded), user_account);
+
+        let path = std::env::temp_dir().join(format!("dlob-{}.json", Pubkey::new_unique()));
+        snapshot.save_json(&path).unwrap();
+        assert_eq!(DLOBSnapshot::load_json(&path).unwrap(), snapshot);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(DLOB::from_snapshot(&DLOBSnapshot::default()).size(), (0, 0));
+    }
+}
@@ -51,6 +51,13 @@ impl EventEmitter {
         });
     }
 
+    /// Returns true if `event_type` has any subscribers
+    pub fn has_subscribers(&self, event_type: &'static str) -> bool {
+        self.subscribers
+            .lock()
+            .is_ok_and(|subs| subs.get(event_type).is_some_and(|h| !h.is_empty()))
+    }
+
     pub fn emit(&self, event_type: &'static str, event: Box<dyn Event>) {
         if let Ok(subs) = self.subscribers.lock() {
             if let Some(handlers) = subs.get(event_type) {
@@ -0,0 +1,497 @@
+//! Persistence and replay of drift events
+//!
+//! Record a live stream with `DriftEventStream::with_sink` and replay it later with `EventSource`
+//! e.g. to drive strategy tests deterministically from production event flow
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::{stream, Stream};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{channel, error::TrySendError, Receiver, Sender};
+
+use crate::{
+    event_subscriber::DriftEvent,
+    types::{SdkError, SdkResult},
+};
+
+/// max. events written to a sink at once
+const SINK_BATCH_SIZE: usize = 256;
+/// max. events queued per sink, events are dropped while a sink is this far behind
+const SINK_CHANNEL_SIZE: usize = 8_192;
+/// rows read at once when replaying from SQLite
+#[cfg(feature = "sqlite")]
+const SQLITE_PAGE_SIZE: usize = 1_024;
+
+/// Receives events from a `DriftEventStream`
+///
+/// Sinks run on a dedicated blocking thread, writes may block
+pub trait EventSink: Send + 'static {
+    /// Record `event`
+    fn write(&mut self, event: &RecordedEvent) -> SdkResult<()>;
+    /// Record a batch of `events`, in order
+    fn write_batch(&mut self, events: &[RecordedEvent]) -> SdkResult<()> {
+        for event in events {
+            self.write(event)?;
+        }
+        Ok(())
+    }
+    /// Flush any buffered events
+    fn flush(&mut self) -> SdkResult<()> {
+        Ok(())
+    }
+}
+
+/// Sends events to a sink running on a blocking thread
+pub(crate) struct SinkSender {
+    tx: Sender<RecordedEvent>,
+    /// number of events dropped as the sink was full
+    dropped: u64,
+}
+
+impl SinkSender {
+    /// Queue `event` for the sink without waiting
+    ///
+    /// The event is dropped if the sink is `SINK_CHANNEL_SIZE` events behind
+    pub(crate) fn send(&mut self, event: RecordedEvent) {
+        match self.tx.try_send(event) {
+            Ok(()) => (),
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                warn!(target: "events", "event sink full, dropped events: {}", self.dropped);
+            }
+            // only if the sink thread panicked
+            Err(TrySendError::Closed(_)) => (),
+        }
+    }
+}
+
+/// Run `sink` on a blocking thread, returns a channel to send it events
+///
+/// Events are written in batches as they arrive, the sink is flushed after each batch and
+/// once the channel closes
+pub(crate) fn spawn_sink(mut sink: impl EventSink) -> SinkSender {
+    let (tx, mut rx) = channel(SINK_CHANNEL_SIZE);
+    tokio::task::spawn_blocking(move || {
+        let mut batch = Vec::with_capacity(SINK_BATCH_SIZE);
+        while next_batch(&mut rx, &mut batch) {
+            if let Err(err) = sink.write_batch(batch.as_slice()) {
+                warn!(target: "events", "event sink write: {err:?}");
+            }
+            batch.clear();
+            if let Err(err) = sink.flush() {
+                warn!(target: "events", "event sink flush: {err:?}");
+            }
+        }
+    });
+
+    SinkSender { tx, dropped: 0 }
+}
+
+/// Wait for the next events from `rx` into `batch`, returns false once the channel is closed
+fn next_batch(rx: &mut Receiver<RecordedEvent>, batch: &mut Vec<RecordedEvent>) -> bool {
+    let Some(event) = rx.blocking_recv() else {
+        return false;
+    };
+    batch.push(event);
+    while batch.len() < SINK_BATCH_SIZE {
+        match rx.try_recv() {
+            Ok(event) => batch.push(event),
+            Err(_) => break,
+        }
+    }
+    true
+}
+
+/// A drift event with the time it was recorded
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// unix timestamp (ms) the event was consumed from the stream
+    pub recorded_at: u64,
+    pub event: DriftEvent,
+}
+
+impl RecordedEvent {
+    /// Record `event` at the current time
+    pub fn now(event: DriftEvent) -> Self {
+        Self {
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            event,
+        }
+    }
+}
+
+/// Writes events as JSON lines, one `RecordedEvent` per line
+pub struct JsonLinesSink<W: Write + Send + 'static = BufWriter<File>> {
+    writer: W,
+}
+
+impl JsonLinesSink {
+    /// Append events to the file at `path`, it is created if missing
+    pub fn create(path: impl AsRef<Path>) -> SdkResult<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write + Send + 'static> JsonLinesSink<W> {
+    /// Write events to `writer`
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+    /// Return the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send + 'static> EventSink for JsonLinesSink<W> {
+    fn write(&mut self, event: &RecordedEvent) -> SdkResult<()> {
+        serde_json::to_writer(&mut self.writer, event).map_err(std::io::Error::from)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+    fn flush(&mut self) -> SdkResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes events to a SQLite database
+///
+/// Events are stored as JSON in the `drift_events` table, indexed by kind and tx signature
+#[cfg(feature = "sqlite")]
+pub struct SqliteSink {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteSink {
+    /// Open the database at `path`, it is created if missing
+    pub fn open(path: impl AsRef<Path>) -> SdkResult<Self> {
+        let conn = rusqlite::Connection::open(path)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+            CREATE TABLE IF NOT EXISTS drift_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recorded_at INTEGER NOT NULL,
+                kind TEXT NOT NULL,
+                signature TEXT NOT NULL,
+                event TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS drift_events_kind ON drift_events (kind);
+            CREATE INDEX IF NOT EXISTS drift_events_signature ON drift_events (signature);",
+        )?;
+        Ok(Self { conn })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl EventSink for SqliteSink {
+    fn write(&mut self, event: &RecordedEvent) -> SdkResult<()> {
+        self.write_batch(std::slice::from_ref(event))
+    }
+    /// Insert `events` in a single transaction
+    fn write_batch(&mut self, events: &[RecordedEvent]) -> SdkResult<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO drift_events (recorded_at, kind, signature, event) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for RecordedEvent { recorded_at, event } in events {
+                insert.execute(rusqlite::params![
+                    *recorded_at as i64,
+                    format!("{:?}", event.kind()),
+                    event.signature(),
+                    serde_json::to_string(event).map_err(std::io::Error::from)?,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Timing of events replayed from an `EventSource`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ReplayTiming {
+    /// emit events as fast as they are consumed
+    Immediate,
+    /// emit events with the delays they were recorded with
+    Original,
+    /// emit events with the recorded delays divided by the given factor
+    Accelerated(f64),
+}
+
+/// Replays recorded drift events
+///
+/// Events are read lazily as they are replayed, iterate the source directly to handle
+/// unreadable events
+pub struct EventSource {
+    events: Box<dyn Iterator<Item = SdkResult<RecordedEvent>> + Send>,
+}
+
+impl EventSource {
+    /// Replay `events` in the given order
+    pub fn new(events: Vec<RecordedEvent>) -> Self {
+        Self {
+            events: Box::new(events.into_iter().map(Ok)),
+        }
+    }
+    /// Read events recorded by a `JsonLinesSink` at `path`
+    ///
+    /// Events that fail to parse are reported with their line number
+    pub fn json_lines(path: impl AsRef<Path>) -> SdkResult<Self> {
+        let lines = BufReader::new(File::open(path)?).lines();
+        let events = lines.enumerate().filter_map(|(idx, line)| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => {
+                Some(serde_json::from_str(&line).map_err(|err| SdkError::EventRecord(idx + 1, err)))
+            }
+            Err(err) => Some(Err(err.into())),
+        });
+
+        Ok(Self {
+            events: Box::new(events),
+        })
+    }
+    /// Read events recorded by a `SqliteSink` at `path`
+    ///
+    /// Events that fail to parse are reported with their row id
+    #[cfg(feature = "sqlite")]
+    pub fn sqlite(path: impl AsRef<Path>) -> SdkResult<Self> {
+        Ok(Self {
+            events: Box::new(SqliteEvents {
+                conn: rusqlite::Connection::open(path)?,
+                last_id: 0,
+                page: Vec::default().into_iter(),
+            }),
+        })
+    }
+    /// Replay the recorded events as a stream with the given `timing`
+    ///
+    /// The stream ends early at the first event that can not be read
+    pub fn replay(self, timing: ReplayTiming) -> impl Stream<Item = DriftEvent> {
+        stream::unfold(
+            (self, None::<u64>),
+            move |(mut events, last_recorded_at)| async move {
+                let RecordedEvent { recorded_at, event } = match events.next()? {
+                    Ok(record) => record,
+                    Err(err) => {
+                        error!(target: "events", "event replay failed, ending stream: {err}");
+                        return None;
+                    }
+                };
+                if let Some(last_recorded_at) = last_recorded_at {
+                    let delay_ms = recorded_at.saturating_sub(last_recorded_at) as f64;
+                    let delay_ms = match timing {
+                        ReplayTiming::Immediate => 0.0,
+                        ReplayTiming::Original => delay_ms,
+                        ReplayTiming::Accelerated(factor) if factor > 0.0 => delay_ms / factor,
+                        ReplayTiming::Accelerated(_) => 0.0,
+                    };
+                    if delay_ms > 0.0 {
+                        tokio::time::sleep(Duration::from_secs_f64(delay_ms / 1_000.0)).await;
+                    }
+                }
+                Some((event, (events, Some(recorded_at))))
+            },
+        )
+    }
+}
+
+impl Iterator for EventSource {
+    type Item = SdkResult<RecordedEvent>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.next()
+    }
+}
+
+/// Reads events from a `SqliteSink` database, a page of rows at a time
+#[cfg(feature = "sqlite")]
+struct SqliteEvents {
+    conn: rusqlite::Connection,
+    /// id of the last row read
+    last_id: i64,
+    /// (id, recorded_at, event) of rows read but not yet returned
+    page: std::vec::IntoIter<(i64, i64, String)>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteEvents {
+    /// Read the rows following `last_id`
+    fn next_page(&self) -> SdkResult<Vec<(i64, i64, String)>> {
+        let mut query = self.conn.prepare_cached(
+            "SELECT id, recorded_at, event FROM drift_events WHERE id > ?1 ORDER BY id ASC LIMIT ?2",
+        )?;
+        let rows = query.query_map(
+            rusqlite::params![self.last_id, SQLITE_PAGE_SIZE as i64],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Iterator for SqliteEvents {
+    type Item = SdkResult<RecordedEvent>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.as_slice().is_empty() {
+            match self.next_page() {
+                Ok(page) => self.page = page.into_iter(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        let (id, recorded_at, event) = self.page.next()?;
+        self.last_id = id;
+        let event =
+            serde_json::from_str(&event).map_err(|err| SdkError::EventRecord(id as usize, err));
+        Some(event.map(|event| RecordedEvent {
+            recorded_at: recorded_at as u64,
+            event,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use futures_util::StreamExt;
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    fn test_event(amount: i64) -> DriftEvent {
+        DriftEvent::FundingPayment {
+            amount,
+            market_index: 1,
+            user: Pubkey::new_unique(),
+            ts: 1_710_000_000,
+            signature: "sig".into(),
+            tx_idx: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn json_lines_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("events-{}.jsonl", Pubkey::new_unique()));
+        let events: Vec<DriftEvent> = (0..3).map(test_event).collect();
+
+        let mut sink = JsonLinesSink::create(&path).unwrap();
+        for event in events.iter() {
+            sink.write(&RecordedEvent::now(event.clone())).unwrap();
+        }
+        sink.flush().unwrap();
+
+        let replayed: Vec<DriftEvent> = EventSource::json_lines(&path)
+            .unwrap()
+            .replay(ReplayTiming::Immediate)
+            .collect()
+            .await;
+        assert_eq!(replayed, events);
+
+        // unreadable events are reported with their line number
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"\n{}\n").unwrap();
+        let records: Vec<SdkResult<RecordedEvent>> =
+            EventSource::json_lines(&path).unwrap().collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 4);
+        assert!(matches!(records[3], Err(SdkError::EventRecord(5, _))));
+    }
+
+    #[tokio::test]
+    async fn spawned_sink() {
+        let path = std::env::temp_dir().join(format!("events-{}.jsonl", Pubkey::new_unique()));
+        let events: Vec<DriftEvent> = (0..3).map(test_event).collect();
+
+        let mut sink = spawn_sink(JsonLinesSink::create(&path).unwrap());
+        for event in events.iter() {
+            sink.send(RecordedEvent::now(event.clone()));
+        }
+        // the sink is flushed once the channel closes
+        drop(sink);
+
+        let start = Instant::now();
+        let records = loop {
+            // lines may be partially written
+            match EventSource::json_lines(&path)
+                .and_then(|source| source.collect::<SdkResult<Vec<_>>>())
+            {
+                Ok(records) if records.len() == 3 => break records,
+                _ if start.elapsed() > Duration::from_secs(2) => panic!("sink not flushed"),
+                _ => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        std::fs::remove_file(&path).unwrap();
+        let replayed: Vec<DriftEvent> = EventSource::new(records)
+            .replay(ReplayTiming::Immediate)
+            .collect()
+            .await;
+        assert_eq!(replayed, events);
+    }
+
+    #[test]
+    fn full_sink_drops_events() {
+        let (tx, _rx) = channel(1);
+        let mut sink = SinkSender { tx, dropped: 0 };
+        for amount in 0..3 {
+            sink.send(RecordedEvent::now(test_event(amount)));
+        }
+        assert_eq!(sink.dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn replay_timing() {
+        let events: Vec<RecordedEvent> = (0..3)
+            .map(|i| RecordedEvent {
+                recorded_at: 1_000 + i as u64 * 200,
+                event: test_event(i),
+            })
+            .collect();
+
+        let start = Instant::now();
+        let replayed: Vec<DriftEvent> = EventSource::new(events.clone())
+            .replay(ReplayTiming::Original)
+            .collect()
+            .await;
+        assert_eq!(replayed.len(), 3);
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        let start = Instant::now();
+        let replayed: Vec<DriftEvent> = EventSource::new(events)
+            .replay(ReplayTiming::Accelerated(100.0))
+            .collect()
+            .await;
+        assert_eq!(replayed.len(), 3);
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("events-{}.db", Pubkey::new_unique()));
+        let events: Vec<DriftEvent> = (0..3).map(test_event).collect();
+
+        let mut sink = SqliteSink::open(&path).unwrap();
+        let records: Vec<RecordedEvent> = events.iter().cloned().map(RecordedEvent::now).collect();
+        sink.write_batch(records.as_slice()).unwrap();
+        drop(sink);
+
+        let replayed: Vec<DriftEvent> = EventSource::sqlite(&path)
+            .unwrap()
+            .replay(ReplayTiming::Immediate)
+            .collect()
+            .await;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replayed, events);
+    }
+}
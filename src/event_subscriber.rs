@@ -1,5 +1,6 @@
 use std::{
     collections::VecDeque,
+    future::Future,
     str::FromStr,
     sync::{Arc, OnceLock},
     task::{Context, Poll},
@@ -11,16 +12,21 @@ use drift::{
     controller::position::PositionDirection,
     state::{
         events::{
-            FundingPaymentRecord, OrderAction, OrderActionExplanation, OrderActionRecord,
-            OrderRecord,
+            CurveRecord, DepositDirection, DepositExplanation, DepositRecord, FundingPaymentRecord,
+            FundingRateRecord, InsuranceFundRecord, InsuranceFundStakeRecord, LPAction, LPRecord,
+            LiquidationRecord, LiquidationType, NewUserRecord, OrderAction, OrderActionExplanation,
+            OrderActionRecord, OrderRecord, SettlePnlExplanation, SettlePnlRecord,
+            SpotInterestRecord, SwapRecord,
         },
+        insurance_fund_stake::StakeAction,
         user::{MarketType, Order},
     },
 };
 use fnv::FnvHashSet;
 use futures_util::{future::BoxFuture, stream::FuturesOrdered, FutureExt, Stream, StreamExt};
-use log::{debug, warn};
+use log::{debug, error, warn};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 pub use solana_client::nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient};
 use solana_client::{
     nonblocking::pubsub_client::PubsubClientError,
@@ -32,6 +38,7 @@ use solana_sdk::{pubkey::Pubkey, signature::Signature, transaction::VersionedTra
 use solana_transaction_status::{
     option_serializer::OptionSerializer, EncodedTransactionWithStatusMeta, UiTransactionEncoding,
 };
+use thiserror::Error;
 use tokio::{
     sync::{
         mpsc::{channel, Receiver, Sender},
@@ -43,10 +50,22 @@ use tokio::{
 use crate::{
     async_utils::{retry_policy::TaskRetryPolicy, spawn_retry_task},
     constants,
-    types::SdkResult,
+    event_sink::{spawn_sink, EventSink, RecordedEvent, SinkSender},
+    serde_helpers::{serde_order, serde_variant},
+    types::{MarketId, SdkResult},
 };
 
 const LOG_TARGET: &str = "events";
+/// buffered events per stream, sized for program-wide streams
+const EVENT_CHANNEL_SIZE: usize = 1024;
+/// max. signatures per page when backfilling (RPC limit)
+const BACKFILL_PAGE_SIZE: usize = 1_000;
+/// max. concurrent tx requests when backfilling
+const BACKFILL_CONCURRENCY: usize = 8;
+/// attempts per RPC request when backfilling, before the stream ends
+const BACKFILL_ATTEMPTS: usize = 5;
+/// delay before the first retry of a failed RPC request when backfilling, doubles per attempt
+const BACKFILL_RETRY_DELAY: Duration = Duration::from_millis(200);
 
 impl EventRpcProvider for RpcClient {
     fn get_tx(
@@ -91,6 +110,30 @@ impl EventRpcProvider for RpcClient {
         }
         .boxed()
     }
+    fn get_tx_signatures_page(
+        &self,
+        account: Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: Option<usize>,
+    ) -> BoxFuture<SdkResult<Vec<(String, u64)>>> {
+        async move {
+            let results = self
+                .get_signatures_for_address_with_config(
+                    &account,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+
+            Ok(results.into_iter().map(|r| (r.signature, r.slot)).collect())
+        }
+        .boxed()
+    }
 }
 
 /// RPC functions required for drift event subscriptions
@@ -104,6 +147,17 @@ pub trait EventRpcProvider: Send + Sync + 'static {
         after: Option<Signature>,
         limit: Option<usize>,
     ) -> BoxFuture<SdkResult<Vec<String>>>;
+    /// Fetch a page of tx signatures of account with their slot, ordered newest to oldest
+    /// `before` only return txs older than this signature, if given
+    /// `until` only return txs more recent than this signature, if given
+    /// `limit` return at most this many signatures, if given
+    fn get_tx_signatures_page(
+        &self,
+        account: Pubkey,
+        before: Option<Signature>,
+        until: Option<Signature>,
+        limit: Option<usize>,
+    ) -> BoxFuture<SdkResult<Vec<(String, u64)>>>;
     /// Fetch tx with `signature`
     fn get_tx(
         &self,
@@ -111,7 +165,7 @@ pub trait EventRpcProvider: Send + Sync + 'static {
     ) -> BoxFuture<SdkResult<EncodedTransactionWithStatusMeta>>;
 }
 
-/// Provides sub-account event streaming
+/// Provides drift event streaming for a sub-account or program-wide
 pub struct EventSubscriber;
 
 impl EventSubscriber {
@@ -123,11 +177,132 @@ impl EventSubscriber {
         sub_account: Pubkey,
         retry_policy: impl TaskRetryPolicy,
     ) -> SdkResult<DriftEventStream> {
-        log_stream(endpoint, sub_account, retry_policy).await
+        log_stream(
+            endpoint,
+            sub_account,
+            EventFilter::sub_account(sub_account),
+            retry_policy,
+        )
+        .await
     }
     /// Subscribe to drift events of `sub_account`, backed by RPC polling APIs
     pub fn subscribe_polled(provider: impl EventRpcProvider, account: Pubkey) -> DriftEventStream {
-        polled_stream(provider, account)
+        polled_stream(provider, account, EventFilter::sub_account(account))
+    }
+    /// Subscribe to all drift program events matching `filter`, backed by Ws APIs
+    ///
+    /// The underlying stream will reconnect according to the given `retry_policy`
+    pub async fn subscribe_program(
+        endpoint: &str,
+        filter: EventFilter,
+        retry_policy: impl TaskRetryPolicy,
+    ) -> SdkResult<DriftEventStream> {
+        log_stream(endpoint, constants::PROGRAM_ID, filter, retry_policy).await
+    }
+    /// Subscribe to all drift program events matching `filter`, backed by RPC polling APIs
+    pub fn subscribe_program_polled(
+        provider: impl EventRpcProvider,
+        filter: EventFilter,
+    ) -> DriftEventStream {
+        polled_stream(provider, constants::PROGRAM_ID, filter)
+    }
+    /// Fetch historic drift events of `account` (or the program, given `PROGRAM_ID`), backed by RPC polling APIs
+    ///
+    /// Events are emitted in chronological order from txs between `from` and `until`.
+    /// If `until` is `None` the stream hands off to live polling once caught up, without gaps or duplicates
+    /// e.g. to resume from the last signature processed before a restart
+    ///
+    /// Signature bounds are exclusive, slot bounds are inclusive.
+    /// The stream ends early if RPC requests keep failing, rather than skip txs
+    pub fn backfill(
+        provider: impl EventRpcProvider,
+        account: Pubkey,
+        from: TxCursor,
+        until: Option<TxCursor>,
+    ) -> DriftEventStream {
+        backfill_stream(provider, account, from, until)
+    }
+}
+
+/// Position in the tx history of an account
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TxCursor {
+    Signature(Signature),
+    Slot(u64),
+}
+
+/// Kind of a `DriftEvent`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DriftEventKind {
+    OrderFill,
+    OrderCancel,
+    OrderCancelMissing,
+    OrderCreate,
+    OrderExpire,
+    FundingPayment,
+    Deposit,
+    Liquidation,
+    SettlePnl,
+    Swap,
+    Lp,
+    InsuranceFund,
+    InsuranceFundStake,
+    NewUser,
+    Curve,
+    SpotInterest,
+    FundingRate,
+}
+
+/// Predicates for selecting events from a stream
+///
+/// Each predicate is optional i.e. matches all events when unset, an event must satisfy all set predicates
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    markets: Vec<MarketId>,
+    kinds: FnvHashSet<DriftEventKind>,
+    users: FnvHashSet<Pubkey>,
+    /// events are from txs of a single sub-account, so those without user info belong to it
+    single_account: bool,
+}
+
+impl EventFilter {
+    /// Create a filter matching all events
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Create a filter matching events of `sub_account`, given a stream of its txs only
+    pub(crate) fn sub_account(sub_account: Pubkey) -> Self {
+        Self {
+            single_account: true,
+            ..Self::new().users([sub_account])
+        }
+    }
+    /// Only match events of the given `markets`
+    ///
+    /// events without market info e.g. `OrderCancelMissing`, `Liquidation`, `NewUser` always match
+    pub fn markets(mut self, markets: impl IntoIterator<Item = MarketId>) -> Self {
+        self.markets.extend(markets);
+        self
+    }
+    /// Only match events of the given `kinds`
+    pub fn kinds(mut self, kinds: impl IntoIterator<Item = DriftEventKind>) -> Self {
+        self.kinds.extend(kinds);
+        self
+    }
+    /// Only match events involving any of the given sub-accounts (or authorities)
+    pub fn users(mut self, users: impl IntoIterator<Item = Pubkey>) -> Self {
+        self.users.extend(users);
+        self
+    }
+    /// Return true if `event` satisfies the filter
+    pub fn matches(&self, event: &DriftEvent) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&event.kind()))
+            && (self.markets.is_empty()
+                || !event.has_market()
+                || self.markets.iter().any(|m| event.involves_market(*m)))
+            && (self.users.is_empty()
+                || self.users.iter().any(|u| event.pertains_to(*u))
+                || (self.single_account && matches!(event, DriftEvent::OrderCancelMissing { .. })))
     }
 }
 
@@ -135,7 +310,9 @@ struct LogEventStream {
     cache: Arc<RwLock<TxSignatureCache>>,
     endpoint: Arc<String>,
     provider: Arc<PubsubClient>,
-    sub_account: Pubkey,
+    /// subscribe to logs of txs mentioning this account
+    account: Pubkey,
+    filter: EventFilter,
     event_tx: Sender<DriftEvent>,
     commitment: CommitmentConfig,
 }
@@ -143,12 +320,12 @@ struct LogEventStream {
 impl LogEventStream {
     /// Returns a future for running the configured log event stream
     async fn stream_fn(mut self) {
-        let sub_account = self.sub_account;
+        let account = self.account;
         let subscribe_result = self
             .provider
             .logs_subscribe(
                 solana_client::rpc_config::RpcTransactionLogsFilter::Mentions(vec![self
-                    .sub_account
+                    .account
                     .to_string()]),
                 RpcTransactionLogsConfig {
                     commitment: Some(self.commitment),
@@ -161,32 +338,32 @@ impl LogEventStream {
             match err {
                 PubsubClientError::ConnectionClosed(_) => {
                     drop(subscribe_result);
-                    warn!(target: LOG_TARGET, "log stream disconnected, reconnecting: {sub_account:?}");
+                    warn!(target: LOG_TARGET, "log stream disconnected, reconnecting: {account:?}");
                     let _ = PubsubClient::new(&self.endpoint)
                         .await
                         .map(|provider| {
                             self.provider = Arc::new(provider);
                         })
                         .map_err(|err| {
-                            warn!(target: LOG_TARGET, "log stream reconnect failed {err:?}, retrying: {sub_account:?}");
+                            warn!(target: LOG_TARGET, "log stream reconnect failed {err:?}, retrying: {account:?}");
                         });
                     return;
                 }
                 subscribe_err => {
-                    warn!(target: LOG_TARGET, "log subscription failed {subscribe_err:?}, retrying: {sub_account:?}");
+                    warn!(target: LOG_TARGET, "log subscription failed {subscribe_err:?}, retrying: {account:?}");
                     return;
                 }
             }
         }
 
         let (mut log_stream, unsub_fn) = subscribe_result.unwrap();
-        debug!(target: LOG_TARGET, "start log subscription: {sub_account:?}");
+        debug!(target: LOG_TARGET, "start log subscription: {account:?}");
 
         while let Some(response) = log_stream.next().await {
             self.process_log(response.value).await;
         }
 
-        warn!(target: LOG_TARGET, "log stream ended: {sub_account:?}");
+        warn!(target: LOG_TARGET, "log stream ended: {account:?}");
         unsub_fn().await;
     }
 
@@ -205,28 +382,40 @@ impl LogEventStream {
             return;
         }
         cache.insert(signature.clone());
+        // don't hold the lock while waiting on the consumer
+        drop(cache);
 
         for (tx_idx, log) in response.logs.iter().enumerate() {
             // a drift sub-account should not interact with any other program by definition
-            if let Some(event) = try_parse_log(log.as_str(), &signature, tx_idx) {
-                // unrelated events from same tx should not be emitted e.g. a filler tx which produces other fill events
-                if event.pertains_to(self.sub_account) {
-                    self.event_tx.try_send(event).expect("sent");
+            match try_parse_log(log.as_str(), &signature, tx_idx) {
+                Ok(Some(event)) => {
+                    // unrelated events from same tx should not be emitted e.g. a filler tx which produces other fill events
+                    if self.filter.matches(&event) {
+                        // consumer hung up, the stream task will be aborted
+                        let _ = self.event_tx.send(event).await;
+                    }
                 }
+                Ok(None) => (),
+                Err(err) => warn!(target: LOG_TARGET, "{err}, tx: {signature:?}"),
             }
         }
     }
 }
 
 /// Creates a poll-ed stream using JSON-RPC interfaces
-fn polled_stream(provider: impl EventRpcProvider, sub_account: Pubkey) -> DriftEventStream {
-    let (event_tx, event_rx) = channel(64);
+fn polled_stream(
+    provider: impl EventRpcProvider,
+    account: Pubkey,
+    filter: EventFilter,
+) -> DriftEventStream {
+    let (event_tx, event_rx) = channel(EVENT_CHANNEL_SIZE);
     let cache = Arc::new(RwLock::new(TxSignatureCache::new(128)));
     let join_handle = tokio::spawn(
         PolledEventStream {
             cache: Arc::clone(&cache),
             provider,
-            sub_account,
+            account,
+            filter,
             event_tx,
         }
         .stream_fn(),
@@ -235,17 +424,61 @@ fn polled_stream(provider: impl EventRpcProvider, sub_account: Pubkey) -> DriftE
     DriftEventStream {
         rx: event_rx,
         task: join_handle,
+        sinks: vec![],
+    }
+}
+
+/// Creates a poll-ed stream of historic events, optionally continuing with live events
+fn backfill_stream(
+    provider: impl EventRpcProvider,
+    account: Pubkey,
+    from: TxCursor,
+    until: Option<TxCursor>,
+) -> DriftEventStream {
+    let (event_tx, event_rx) = channel(EVENT_CHANNEL_SIZE);
+    let filter = if account == constants::PROGRAM_ID {
+        EventFilter::new()
+    } else {
+        EventFilter::sub_account(account)
+    };
+    let join_handle = tokio::spawn(async move {
+        let stream = PolledEventStream {
+            cache: Arc::new(RwLock::new(TxSignatureCache::new(128))),
+            provider,
+            account,
+            filter,
+            event_tx,
+        };
+        let backfill = async {
+            let last_seen_tx = stream.backfill(from, until).await?;
+            match until {
+                Some(_) => Ok(None),
+                None => stream.catch_up(last_seen_tx).await.map(Some),
+            }
+        };
+        match backfill.await {
+            Ok(Some(last_seen_tx)) => stream.poll_from(last_seen_tx).await,
+            Ok(None) => (),
+            Err(err) => error!(target: LOG_TARGET, "backfill failed, ending stream: {err:?}"),
+        }
+    });
+
+    DriftEventStream {
+        rx: event_rx,
+        task: join_handle,
+        sinks: vec![],
     }
 }
 
 /// Creates a Ws-backed event stream using `logsSubscribe` interface
 async fn log_stream(
     endpoint: &str,
-    sub_account: Pubkey,
+    account: Pubkey,
+    filter: EventFilter,
     retry_policy: impl TaskRetryPolicy,
 ) -> SdkResult<DriftEventStream> {
-    debug!(target: LOG_TARGET, "stream events for {sub_account:?}");
-    let (event_tx, event_rx) = channel(64);
+    debug!(target: LOG_TARGET, "stream events for {account:?}");
+    let (event_tx, event_rx) = channel(EVENT_CHANNEL_SIZE);
 
     let provider = Arc::new(PubsubClient::new(endpoint).await?);
     let cache = Arc::new(RwLock::new(TxSignatureCache::new(256)));
@@ -258,7 +491,8 @@ async fn log_stream(
                 endpoint: Arc::clone(&endpoint),
                 cache: Arc::clone(&cache),
                 provider: Arc::clone(&provider),
-                sub_account,
+                account,
+                filter: filter.clone(),
                 event_tx: event_tx.clone(),
                 commitment: CommitmentConfig::confirmed(),
             };
@@ -270,6 +504,7 @@ async fn log_stream(
     Ok(DriftEventStream {
         rx: event_rx,
         task: join_handle,
+        sinks: vec![],
     })
 }
 
@@ -277,31 +512,40 @@ pub struct PolledEventStream<T: EventRpcProvider> {
     cache: Arc<RwLock<TxSignatureCache>>,
     event_tx: Sender<DriftEvent>,
     provider: T,
-    sub_account: Pubkey,
+    /// poll txs of this account
+    account: Pubkey,
+    filter: EventFilter,
 }
 
 impl<T: EventRpcProvider> PolledEventStream<T> {
     async fn stream_fn(self) {
-        debug!(target: LOG_TARGET, "poll events for {:?}", self.sub_account);
+        debug!(target: LOG_TARGET, "poll events for {:?}", self.account);
         // poll for events in any tx after this tx
         // initially fetch the most recent tx from account
         debug!(target: LOG_TARGET, "fetch initial txs");
         let res = self
             .provider
-            .get_tx_signatures(self.sub_account, None, Some(1))
+            .get_tx_signatures(self.account, None, Some(1))
             .await;
         debug!(target: LOG_TARGET, "fetched initial txs");
 
-        let mut last_seen_tx = res.expect("fetched tx").first().cloned();
+        let last_seen_tx = res.expect("fetched tx").first().cloned();
+        self.poll_from(last_seen_tx).await
+    }
+    /// Poll for events in any tx more recent than `last_seen_tx`
+    async fn poll_from(&self, mut last_seen_tx: Option<String>) {
         let provider_ref = &self.provider;
         'outer: loop {
             // don't needlessly spam the RPC or hog the executor
             tokio::time::sleep(Duration::from_millis(400)).await;
+            if self.event_tx.is_closed() {
+                return;
+            }
 
             debug!(target: LOG_TARGET, "poll txs for events");
             let signatures = provider_ref
                 .get_tx_signatures(
-                    self.sub_account,
+                    self.account,
                     last_seen_tx
                         .clone()
                         .map(|s| Signature::from_str(&s).unwrap()),
@@ -347,47 +591,192 @@ impl<T: EventRpcProvider> PolledEventStream<T> {
                 }
 
                 last_seen_tx = Some(signature.clone());
-                {
-                    let mut cache = self.cache.write().await;
-                    if cache.contains(&signature) {
-                        debug!(target: LOG_TARGET, "poll skipping cached tx: {signature:?}");
-                        continue;
-                    }
-                    cache.insert(signature.clone());
-                }
+                self.process_tx(signature.as_str(), response.unwrap()).await;
+            }
+        }
+    }
+    /// Emit events from txs between `from` and `until` in chronological order
+    ///
+    /// Returns the most recent signature of the account seen, if any.
+    /// Fails once an RPC request has failed `BACKFILL_ATTEMPTS` times
+    async fn backfill(&self, from: TxCursor, until: Option<TxCursor>) -> SdkResult<Option<String>> {
+        let (mut before, max_slot) = match until {
+            Some(TxCursor::Signature(signature)) => (Some(signature), u64::MAX),
+            Some(TxCursor::Slot(slot)) => (None, slot),
+            None => (None, u64::MAX),
+        };
+        let (until_signature, min_slot) = match from {
+            TxCursor::Signature(signature) => (Some(signature), 0),
+            TxCursor::Slot(slot) => (None, slot),
+        };
+        let in_range = |page: Vec<(String, u64)>| -> Vec<String> {
+            page.into_iter()
+                .filter(|(_, slot)| (min_slot..=max_slot).contains(slot))
+                .map(|(s, _)| s)
+                .collect()
+        };
+        debug!(target: LOG_TARGET, "backfill events for {:?}, from: {from:?}, until: {until:?}", self.account);
+
+        // walk signatures backwards, pages are ordered newest to oldest
+        // only the newest page is kept, older pages are re-fetched from their cursor when processed
+        let mut most_recent_tx = None;
+        let mut newest_page = Vec::<String>::default();
+        let mut cursors = Vec::<Signature>::default();
+        loop {
+            if self.event_tx.is_closed() {
+                return Ok(None);
+            }
+            let page = self
+                .with_backoff(|| {
+                    self.provider.get_tx_signatures_page(
+                        self.account,
+                        before,
+                        until_signature,
+                        Some(BACKFILL_PAGE_SIZE),
+                    )
+                })
+                .await?;
+            let Some((oldest_signature, oldest_slot)) = page.last().cloned() else {
+                break;
+            };
+            let has_txs_in_range = page
+                .iter()
+                .any(|(_, slot)| (min_slot..=max_slot).contains(slot));
+            if most_recent_tx.is_none() {
+                most_recent_tx = page.first().map(|(s, _)| s.clone());
+                newest_page = in_range(page);
+            } else if has_txs_in_range {
+                cursors.push(before.expect("older pages have a cursor"));
+            }
+            if oldest_slot < min_slot {
+                break;
+            }
+            before = Some(Signature::from_str(&oldest_signature).expect("valid signature"));
+        }
+        debug!(target: LOG_TARGET, "backfill {} pages", cursors.len() + 1);
 
-                let EncodedTransactionWithStatusMeta {
-                    meta, transaction, ..
-                } = response.unwrap();
-                if meta.is_none() {
-                    continue;
-                }
-                let meta = meta.unwrap();
+        for before in cursors.into_iter().rev() {
+            if self.event_tx.is_closed() {
+                return Ok(None);
+            }
+            let page = self
+                .with_backoff(|| {
+                    self.provider.get_tx_signatures_page(
+                        self.account,
+                        Some(before),
+                        until_signature,
+                        Some(BACKFILL_PAGE_SIZE),
+                    )
+                })
+                .await?;
+            self.process_page(in_range(page)).await?;
+        }
+        self.process_page(newest_page).await?;
 
-                if let Some(VersionedTransaction { message, .. }) = transaction.decode() {
-                    // only txs interacting with drift program
-                    if !message
-                        .static_account_keys()
-                        .iter()
-                        .any(|k| k == &constants::PROGRAM_ID)
-                    {
-                        continue;
-                    }
+        Ok(most_recent_tx.or(until_signature.map(|s| s.to_string())))
+    }
+    /// Emit events from txs more recent than `last_seen_tx` until there are none left
+    ///
+    /// Txs landing during a backfill may span several pages, unlike live polling these are paged
+    /// through in full. Returns the most recent signature of the account seen, if any
+    async fn catch_up(&self, mut last_seen_tx: Option<String>) -> SdkResult<Option<String>> {
+        while let Some(signature) = last_seen_tx.as_deref() {
+            let from =
+                TxCursor::Signature(Signature::from_str(signature).expect("valid signature"));
+            let most_recent_tx = self.backfill(from, None).await?;
+            if most_recent_tx == last_seen_tx || self.event_tx.is_closed() {
+                break;
+            }
+            last_seen_tx = most_recent_tx;
+        }
+
+        Ok(last_seen_tx)
+    }
+    /// Emit events from the txs `signatures` (newest to oldest) in chronological order
+    async fn process_page(&self, signatures: Vec<String>) -> SdkResult<()> {
+        let mut txs = futures_util::stream::iter(signatures.into_iter().rev())
+            .map(|s| async move {
+                let signature = Signature::from_str(s.as_str()).expect("valid signature");
+                let response = self.with_backoff(|| self.provider.get_tx(signature)).await;
+                (s, response)
+            })
+            .buffered(BACKFILL_CONCURRENCY);
+
+        while let Some((signature, response)) = txs.next().await {
+            self.process_tx(signature.as_str(), response?).await;
+        }
+
+        Ok(())
+    }
+    /// Run the RPC request `f`, retrying with exponential backoff on failure
+    ///
+    /// Returns the last error after `BACKFILL_ATTEMPTS` or once the stream is closed
+    async fn with_backoff<R, F: Future<Output = SdkResult<R>>>(
+        &self,
+        mut f: impl FnMut() -> F,
+    ) -> SdkResult<R> {
+        let mut delay = BACKFILL_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Ok(result) => return Ok(result),
+                Err(err) if attempt >= BACKFILL_ATTEMPTS || self.event_tx.is_closed() => {
+                    return Err(err)
                 }
-                // ignore failed txs
-                if meta.err.is_some() {
-                    continue;
+                Err(err) => {
+                    warn!(target: LOG_TARGET, "backfill request failed ({attempt}/{BACKFILL_ATTEMPTS}): {err:?}");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
                 }
+            }
+        }
+    }
+    /// Emit relevant events of tx `signature`, unless already processed
+    async fn process_tx(&self, signature: &str, response: EncodedTransactionWithStatusMeta) {
+        {
+            let mut cache = self.cache.write().await;
+            if cache.contains(signature) {
+                debug!(target: LOG_TARGET, "poll skipping cached tx: {signature:?}");
+                return;
+            }
+            cache.insert(signature.to_string());
+        }
 
-                if let OptionSerializer::Some(logs) = meta.log_messages {
-                    for (tx_idx, log) in logs.iter().enumerate() {
-                        if let Some(event) = try_parse_log(log.as_str(), signature.as_str(), tx_idx)
-                        {
-                            if event.pertains_to(self.sub_account) {
-                                self.event_tx.try_send(event).expect("sent");
-                            }
+        let EncodedTransactionWithStatusMeta {
+            meta, transaction, ..
+        } = response;
+        let Some(meta) = meta else {
+            return;
+        };
+
+        if let Some(VersionedTransaction { message, .. }) = transaction.decode() {
+            // only txs interacting with drift program
+            if !message
+                .static_account_keys()
+                .iter()
+                .any(|k| k == &constants::PROGRAM_ID)
+            {
+                return;
+            }
+        }
+        // ignore failed txs
+        if meta.err.is_some() {
+            return;
+        }
+
+        if let OptionSerializer::Some(logs) = meta.log_messages {
+            for (tx_idx, log) in logs.iter().enumerate() {
+                match try_parse_log(log.as_str(), signature, tx_idx) {
+                    Ok(Some(event)) => {
+                        if self.filter.matches(&event) {
+                            let _ = self.event_tx.send(event).await;
                         }
                     }
+                    Ok(None) => (),
+                    Err(err) => {
+                        warn!(target: LOG_TARGET, "poll {err}, tx: {signature:?}")
+                    }
                 }
             }
         }
@@ -400,6 +789,8 @@ pub struct DriftEventStream {
     task: JoinHandle<()>,
     /// channel of events from stream task
     rx: Receiver<DriftEvent>,
+    /// channels to sinks receiving a copy of each event
+    sinks: Vec<SinkSender>,
 }
 
 impl DriftEventStream {
@@ -407,36 +798,82 @@ impl DriftEventStream {
     pub fn unsubscribe(&self) {
         self.task.abort();
     }
+    /// Tee events of the stream into `sink` e.g. to record them
+    ///
+    /// Events are recorded as they are consumed from the stream and written on a blocking thread.
+    /// A sink never slows the stream, if it falls too far behind further events are dropped
+    /// (with a warning) until it catches up.
+    /// The sink is flushed and dropped once the stream is dropped
+    pub fn with_sink(mut self, sink: impl EventSink) -> Self {
+        self.sinks.push(spawn_sink(sink));
+        self
+    }
 }
 
 impl Drop for DriftEventStream {
     fn drop(&mut self) {
-        self.unsubscribe()
+        self.unsubscribe();
     }
 }
 
 impl Stream for DriftEventStream {
     type Item = DriftEvent;
-    fn poll_next(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut Context<'_>,
-    ) -> Poll<Option<Self::Item>> {
-        self.as_mut().rx.poll_recv(cx)
+    fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let event = this.rx.poll_recv(cx);
+        if let Poll::Ready(Some(ref event)) = event {
+            if !this.sinks.is_empty() {
+                let record = RecordedEvent::now(event.clone());
+                for sink in this.sinks.iter_mut() {
+                    sink.send(record.clone());
+                }
+            }
+        }
+        event
     }
 }
 
 const PROGRAM_LOG: &str = "Program log: ";
 const PROGRAM_DATA: &str = "Program data: ";
 
+/// Drift event log could not be deserialized
+///
+/// Event logs only fail to decode on a breaking protocol change i.e. the SDK is out of date
+#[derive(Debug, Error)]
+#[error("could not decode {event}: {source}")]
+pub struct EventDecodeError {
+    /// name of the event type
+    pub event: &'static str,
+    #[source]
+    pub source: std::io::Error,
+}
+
+/// Deserialize an event record of type `T`
+fn decode_event<T: AnchorDeserialize>(
+    event: &'static str,
+    data: &mut &[u8],
+) -> Result<T, EventDecodeError> {
+    T::deserialize(data).map_err(|source| EventDecodeError { event, source })
+}
+
 /// Try deserialize a drift event type from raw log string
 /// https://github.com/coral-xyz/anchor/blob/9d947cb26b693e85e1fd26072bb046ff8f95bdcf/client/src/lib.rs#L552
-pub fn try_parse_log(raw: &str, signature: &str, tx_idx: usize) -> Option<DriftEvent> {
+///
+/// Returns `Ok(None)` if the log is not a (supported) drift event
+pub fn try_parse_log(
+    raw: &str,
+    signature: &str,
+    tx_idx: usize,
+) -> Result<Option<DriftEvent>, EventDecodeError> {
     // Log emitted from the current program.
     if let Some(log) = raw
         .strip_prefix(PROGRAM_LOG)
         .or_else(|| raw.strip_prefix(PROGRAM_DATA))
     {
         if let Ok(borsh_bytes) = anchor_lang::__private::base64::decode(log) {
+            if borsh_bytes.len() < 8 {
+                return Ok(None);
+            }
             let (disc, mut data) = borsh_bytes.split_at(8);
             let disc: [u8; 8] = disc.try_into().unwrap();
 
@@ -469,30 +906,35 @@ pub fn try_parse_log(raw: &str, signature: &str, tx_idx: usize) -> Option<DriftE
                 }
             };
 
-            return Some(event);
+            return Ok(Some(event));
         }
     }
 
-    None
+    Ok(None)
 }
 
 static ORDER_CANCEL_MISSING_RE: OnceLock<Regex> = OnceLock::new();
 
 /// Enum of all drift program events
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum DriftEvent {
     OrderFill {
+        #[serde(with = "serde_pubkey::option")]
         maker: Option<Pubkey>,
         maker_fee: i64,
         maker_order_id: u32,
+        #[serde(with = "serde_variant::option")]
         maker_side: Option<PositionDirection>,
+        #[serde(with = "serde_pubkey::option")]
         taker: Option<Pubkey>,
         taker_fee: u64,
         taker_order_id: u32,
+        #[serde(with = "serde_variant::option")]
         taker_side: Option<PositionDirection>,
         base_asset_amount_filled: u64,
         quote_asset_amount_filled: u64,
         market_index: u16,
+        #[serde(with = "serde_variant")]
         market_type: MarketType,
         oracle_price: i64,
         signature: String,
@@ -500,10 +942,15 @@ pub enum DriftEvent {
         ts: u64,
     },
     OrderCancel {
+        #[serde(with = "serde_pubkey::option")]
         taker: Option<Pubkey>,
+        #[serde(with = "serde_pubkey::option")]
         maker: Option<Pubkey>,
         taker_order_id: u32,
         maker_order_id: u32,
+        market_index: u16,
+        #[serde(with = "serde_variant")]
+        market_type: MarketType,
         signature: String,
         tx_idx: usize,
         ts: u64,
@@ -515,7 +962,9 @@ pub enum DriftEvent {
         signature: String,
     },
     OrderCreate {
+        #[serde(with = "serde_order")]
         order: Order,
+        #[serde(with = "serde_pubkey")]
         user: Pubkey,
         ts: u64,
         signature: String,
@@ -524,8 +973,12 @@ pub enum DriftEvent {
     // sub-case of cancel?
     OrderExpire {
         order_id: u32,
+        #[serde(with = "serde_pubkey::option")]
         user: Option<Pubkey>,
         fee: u64,
+        market_index: u16,
+        #[serde(with = "serde_variant")]
+        market_type: MarketType,
         ts: u64,
         signature: String,
         tx_idx: usize,
@@ -533,14 +986,267 @@ pub enum DriftEvent {
     FundingPayment {
         amount: i64,
         market_index: u16,
+        #[serde(with = "serde_pubkey")]
         user: Pubkey,
         ts: u64,
         signature: String,
         tx_idx: usize,
     },
+    Deposit {
+        #[serde(with = "serde_pubkey")]
+        user: Pubkey,
+        #[serde(with = "serde_pubkey")]
+        user_authority: Pubkey,
+        #[serde(with = "serde_variant")]
+        direction: DepositDirection,
+        #[serde(with = "serde_variant")]
+        explanation: DepositExplanation,
+        amount: u64,
+        market_index: u16,
+        oracle_price: i64,
+        /// counterparty sub-account of a transfer between sub-accounts
+        #[serde(with = "serde_pubkey::option")]
+        transfer_user: Option<Pubkey>,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    Liquidation {
+        #[serde(with = "serde_pubkey")]
+        user: Pubkey,
+        #[serde(with = "serde_pubkey")]
+        liquidator: Pubkey,
+        #[serde(with = "serde_variant")]
+        liquidation_type: LiquidationType,
+        liquidation_id: u16,
+        margin_requirement: u128,
+        total_collateral: i128,
+        margin_freed: u64,
+        bankrupt: bool,
+        canceled_order_ids: Vec<u32>,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    SettlePnl {
+        #[serde(with = "serde_pubkey")]
+        user: Pubkey,
+        market_index: u16,
+        pnl: i128,
+        base_asset_amount: i64,
+        quote_asset_amount_after: i64,
+        settle_price: i64,
+        #[serde(with = "serde_variant")]
+        explanation: SettlePnlExplanation,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    Swap {
+        #[serde(with = "serde_pubkey")]
+        user: Pubkey,
+        in_market_index: u16,
+        out_market_index: u16,
+        amount_in: u64,
+        amount_out: u64,
+        in_oracle_price: i64,
+        out_oracle_price: i64,
+        fee: u64,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    /// Perp LP add/remove/settle
+    Lp {
+        #[serde(with = "serde_pubkey")]
+        user: Pubkey,
+        #[serde(with = "serde_variant")]
+        action: LPAction,
+        market_index: u16,
+        n_shares: u64,
+        delta_base_asset_amount: i64,
+        delta_quote_asset_amount: i64,
+        pnl: i64,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    /// Insurance fund revenue settlement/resolution
+    InsuranceFund {
+        spot_market_index: u16,
+        perp_market_index: u16,
+        amount: i64,
+        vault_amount_before: u64,
+        insurance_vault_amount_before: u64,
+        total_if_shares_before: u128,
+        total_if_shares_after: u128,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    InsuranceFundStake {
+        #[serde(with = "serde_pubkey")]
+        user_authority: Pubkey,
+        #[serde(with = "serde_variant")]
+        action: StakeAction,
+        market_index: u16,
+        amount: u64,
+        if_shares_before: u128,
+        if_shares_after: u128,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    NewUser {
+        #[serde(with = "serde_pubkey")]
+        user: Pubkey,
+        #[serde(with = "serde_pubkey")]
+        user_authority: Pubkey,
+        sub_account_id: u16,
+        name: [u8; 32],
+        #[serde(with = "serde_pubkey")]
+        referrer: Pubkey,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    /// AMM curve update e.g. repeg or k change
+    Curve {
+        market_index: u16,
+        record_id: u64,
+        peg_multiplier_before: u128,
+        peg_multiplier_after: u128,
+        sqrt_k_before: u128,
+        sqrt_k_after: u128,
+        oracle_price: i64,
+        adjustment_cost: i128,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    SpotInterest {
+        market_index: u16,
+        deposit_balance: u128,
+        borrow_balance: u128,
+        cumulative_deposit_interest: u128,
+        cumulative_borrow_interest: u128,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
+    FundingRate {
+        market_index: u16,
+        record_id: u64,
+        funding_rate: i64,
+        funding_rate_long: i128,
+        funding_rate_short: i128,
+        oracle_price_twap: i64,
+        mark_price_twap: u64,
+        ts: u64,
+        signature: String,
+        tx_idx: usize,
+    },
 }
 
 impl DriftEvent {
+    /// Return the kind of event
+    pub fn kind(&self) -> DriftEventKind {
+        match self {
+            Self::OrderFill { .. } => DriftEventKind::OrderFill,
+            Self::OrderCancel { .. } => DriftEventKind::OrderCancel,
+            Self::OrderCancelMissing { .. } => DriftEventKind::OrderCancelMissing,
+            Self::OrderCreate { .. } => DriftEventKind::OrderCreate,
+            Self::OrderExpire { .. } => DriftEventKind::OrderExpire,
+            Self::FundingPayment { .. } => DriftEventKind::FundingPayment,
+            Self::Deposit { .. } => DriftEventKind::Deposit,
+            Self::Liquidation { .. } => DriftEventKind::Liquidation,
+            Self::SettlePnl { .. } => DriftEventKind::SettlePnl,
+            Self::Swap { .. } => DriftEventKind::Swap,
+            Self::Lp { .. } => DriftEventKind::Lp,
+            Self::InsuranceFund { .. } => DriftEventKind::InsuranceFund,
+            Self::InsuranceFundStake { .. } => DriftEventKind::InsuranceFundStake,
+            Self::NewUser { .. } => DriftEventKind::NewUser,
+            Self::Curve { .. } => DriftEventKind::Curve,
+            Self::SpotInterest { .. } => DriftEventKind::SpotInterest,
+            Self::FundingRate { .. } => DriftEventKind::FundingRate,
+        }
+    }
+    /// Return the signature of the tx which emitted the event
+    pub fn signature(&self) -> &str {
+        match self {
+            Self::OrderFill { signature, .. }
+            | Self::OrderCancel { signature, .. }
+            | Self::OrderCancelMissing { signature, .. }
+            | Self::OrderCreate { signature, .. }
+            | Self::OrderExpire { signature, .. }
+            | Self::FundingPayment { signature, .. }
+            | Self::Deposit { signature, .. }
+            | Self::Liquidation { signature, .. }
+            | Self::SettlePnl { signature, .. }
+            | Self::Swap { signature, .. }
+            | Self::Lp { signature, .. }
+            | Self::InsuranceFund { signature, .. }
+            | Self::InsuranceFundStake { signature, .. }
+            | Self::NewUser { signature, .. }
+            | Self::Curve { signature, .. }
+            | Self::SpotInterest { signature, .. }
+            | Self::FundingRate { signature, .. } => signature.as_str(),
+        }
+    }
+    /// Return true if the event is connected to `market`
+    ///
+    /// Always false for events without market info
+    pub fn involves_market(&self, market: MarketId) -> bool {
+        match self {
+            Self::OrderFill {
+                market_index,
+                market_type,
+                ..
+            }
+            | Self::OrderCancel {
+                market_index,
+                market_type,
+                ..
+            }
+            | Self::OrderExpire {
+                market_index,
+                market_type,
+                ..
+            } => market == MarketId::from((*market_index, *market_type)),
+            Self::OrderCreate { order, .. } => {
+                market == MarketId::from((order.market_index, order.market_type))
+            }
+            Self::FundingPayment { market_index, .. }
+            | Self::SettlePnl { market_index, .. }
+            | Self::Lp { market_index, .. }
+            | Self::Curve { market_index, .. }
+            | Self::FundingRate { market_index, .. } => market == MarketId::perp(*market_index),
+            Self::Deposit { market_index, .. }
+            | Self::InsuranceFundStake { market_index, .. }
+            | Self::SpotInterest { market_index, .. } => market == MarketId::spot(*market_index),
+            Self::Swap {
+                in_market_index,
+                out_market_index,
+                ..
+            } => {
+                market == MarketId::spot(*in_market_index)
+                    || market == MarketId::spot(*out_market_index)
+            }
+            Self::InsuranceFund {
+                spot_market_index, ..
+            } => market == MarketId::spot(*spot_market_index),
+            Self::OrderCancelMissing { .. } | Self::Liquidation { .. } | Self::NewUser { .. } => {
+                false
+            }
+        }
+    }
+    /// Return true if the event carries market info
+    fn has_market(&self) -> bool {
+        !matches!(
+            self,
+            Self::OrderCancelMissing { .. } | Self::Liquidation { .. } | Self::NewUser { .. }
+        )
+    }
     /// Return true if the event is connected to sub-account
     fn pertains_to(&self, sub_account: Pubkey) -> bool {
         let subject = &Some(sub_account);
@@ -550,8 +1256,27 @@ impl DriftEvent {
             }
             Self::OrderCreate { user, .. } => *user == sub_account,
             Self::OrderExpire { user, .. } => user == subject,
-            Self::OrderCancelMissing { .. } => true,
-            Self::FundingPayment { user, .. } => *user == sub_account,
+            // no user info, see `EventFilter::sub_account`
+            Self::OrderCancelMissing { .. } => false,
+            Self::FundingPayment { user, .. }
+            | Self::SettlePnl { user, .. }
+            | Self::Swap { user, .. }
+            | Self::Lp { user, .. }
+            | Self::NewUser { user, .. } => *user == sub_account,
+            Self::Deposit {
+                user,
+                transfer_user,
+                ..
+            } => *user == sub_account || transfer_user == subject,
+            Self::Liquidation {
+                user, liquidator, ..
+            } => *user == sub_account || *liquidator == sub_account,
+            Self::InsuranceFundStake { user_authority, .. } => *user_authority == sub_account,
+            // market-wide events
+            Self::InsuranceFund { .. }
+            | Self::Curve { .. }
+            | Self::SpotInterest { .. }
+            | Self::FundingRate { .. } => false,
         }
     }
     /// Deserialize drift event by discriminant
@@ -560,21 +1285,74 @@ impl DriftEvent {
         data: &mut &[u8],
         signature: &str,
         tx_idx: usize,
-    ) -> Option<Self> {
-        match disc {
-            // deser should only fail on a breaking protocol changes
-            OrderActionRecord::DISCRIMINATOR => Self::from_oar(
-                OrderActionRecord::deserialize(data).expect("deserializes"),
+    ) -> Result<Option<Self>, EventDecodeError> {
+        // deser should only fail on a breaking protocol changes
+        let event = match disc {
+            OrderActionRecord::DISCRIMINATOR => {
+                Self::from_oar(decode_event("OrderActionRecord", data)?, signature, tx_idx)
+            }
+            OrderRecord::DISCRIMINATOR => {
+                Self::from_order_record(decode_event("OrderRecord", data)?, signature, tx_idx)
+            }
+            FundingPaymentRecord::DISCRIMINATOR => Some(Self::from_funding_payment_record(
+                decode_event("FundingPaymentRecord", data)?,
                 signature,
                 tx_idx,
-            ),
-            OrderRecord::DISCRIMINATOR => Self::from_order_record(
-                OrderRecord::deserialize(data).expect("deserializes"),
+            )),
+            DepositRecord::DISCRIMINATOR => Some(Self::from_deposit_record(
+                decode_event("DepositRecord", data)?,
                 signature,
                 tx_idx,
-            ),
-            FundingPaymentRecord::DISCRIMINATOR => Some(Self::from_funding_payment_record(
-                FundingPaymentRecord::deserialize(data).expect("deserializes"),
+            )),
+            LiquidationRecord::DISCRIMINATOR => Some(Self::from_liquidation_record(
+                decode_event("LiquidationRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            SettlePnlRecord::DISCRIMINATOR => Some(Self::from_settle_pnl_record(
+                decode_event("SettlePnlRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            SwapRecord::DISCRIMINATOR => Some(Self::from_swap_record(
+                decode_event("SwapRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            LPRecord::DISCRIMINATOR => Some(Self::from_lp_record(
+                decode_event("LPRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            InsuranceFundRecord::DISCRIMINATOR => Some(Self::from_insurance_fund_record(
+                decode_event("InsuranceFundRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            InsuranceFundStakeRecord::DISCRIMINATOR => {
+                Some(Self::from_insurance_fund_stake_record(
+                    decode_event("InsuranceFundStakeRecord", data)?,
+                    signature,
+                    tx_idx,
+                ))
+            }
+            NewUserRecord::DISCRIMINATOR => Some(Self::from_new_user_record(
+                decode_event("NewUserRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            CurveRecord::DISCRIMINATOR => Some(Self::from_curve_record(
+                decode_event("CurveRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            SpotInterestRecord::DISCRIMINATOR => Some(Self::from_spot_interest_record(
+                decode_event("SpotInterestRecord", data)?,
+                signature,
+                tx_idx,
+            )),
+            FundingRateRecord::DISCRIMINATOR => Some(Self::from_funding_rate_record(
+                decode_event("FundingRateRecord", data)?,
                 signature,
                 tx_idx,
             )),
@@ -582,6 +1360,174 @@ impl DriftEvent {
                 debug!(target: LOG_TARGET, "unhandled event: {disc:?}");
                 None
             }
+        };
+
+        Ok(event)
+    }
+    fn from_deposit_record(value: DepositRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::Deposit {
+            user: value.user,
+            user_authority: value.user_authority,
+            direction: value.direction,
+            explanation: value.explanation,
+            amount: value.amount,
+            market_index: value.market_index,
+            oracle_price: value.oracle_price,
+            transfer_user: value.transfer_user,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_liquidation_record(value: LiquidationRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::Liquidation {
+            user: value.user,
+            liquidator: value.liquidator,
+            liquidation_type: value.liquidation_type,
+            liquidation_id: value.liquidation_id,
+            margin_requirement: value.margin_requirement,
+            total_collateral: value.total_collateral,
+            margin_freed: value.margin_freed,
+            bankrupt: value.bankrupt,
+            canceled_order_ids: value.canceled_order_ids,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_settle_pnl_record(value: SettlePnlRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::SettlePnl {
+            user: value.user,
+            market_index: value.market_index,
+            pnl: value.pnl,
+            base_asset_amount: value.base_asset_amount,
+            quote_asset_amount_after: value.quote_asset_amount_after,
+            settle_price: value.settle_price,
+            explanation: value.explanation,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_swap_record(value: SwapRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::Swap {
+            user: value.user,
+            in_market_index: value.in_market_index,
+            out_market_index: value.out_market_index,
+            amount_in: value.amount_in,
+            amount_out: value.amount_out,
+            in_oracle_price: value.in_oracle_price,
+            out_oracle_price: value.out_oracle_price,
+            fee: value.fee,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_lp_record(value: LPRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::Lp {
+            user: value.user,
+            action: value.action,
+            market_index: value.market_index,
+            n_shares: value.n_shares,
+            delta_base_asset_amount: value.delta_base_asset_amount,
+            delta_quote_asset_amount: value.delta_quote_asset_amount,
+            pnl: value.pnl,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_insurance_fund_record(
+        value: InsuranceFundRecord,
+        signature: &str,
+        tx_idx: usize,
+    ) -> Self {
+        Self::InsuranceFund {
+            spot_market_index: value.spot_market_index,
+            perp_market_index: value.perp_market_index,
+            amount: value.amount,
+            vault_amount_before: value.vault_amount_before,
+            insurance_vault_amount_before: value.insurance_vault_amount_before,
+            total_if_shares_before: value.total_if_shares_before,
+            total_if_shares_after: value.total_if_shares_after,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_insurance_fund_stake_record(
+        value: InsuranceFundStakeRecord,
+        signature: &str,
+        tx_idx: usize,
+    ) -> Self {
+        Self::InsuranceFundStake {
+            user_authority: value.user_authority,
+            action: value.action,
+            market_index: value.market_index,
+            amount: value.amount,
+            if_shares_before: value.if_shares_before,
+            if_shares_after: value.if_shares_after,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_new_user_record(value: NewUserRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::NewUser {
+            user: value.user,
+            user_authority: value.user_authority,
+            sub_account_id: value.sub_account_id,
+            name: value.name,
+            referrer: value.referrer,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_curve_record(value: CurveRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::Curve {
+            market_index: value.market_index,
+            record_id: value.record_id,
+            peg_multiplier_before: value.peg_multiplier_before,
+            peg_multiplier_after: value.peg_multiplier_after,
+            sqrt_k_before: value.sqrt_k_before,
+            sqrt_k_after: value.sqrt_k_after,
+            oracle_price: value.oracle_price,
+            adjustment_cost: value.adjustment_cost,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_spot_interest_record(
+        value: SpotInterestRecord,
+        signature: &str,
+        tx_idx: usize,
+    ) -> Self {
+        Self::SpotInterest {
+            market_index: value.market_index,
+            deposit_balance: value.deposit_balance,
+            borrow_balance: value.borrow_balance,
+            cumulative_deposit_interest: value.cumulative_deposit_interest,
+            cumulative_borrow_interest: value.cumulative_borrow_interest,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
+        }
+    }
+    fn from_funding_rate_record(value: FundingRateRecord, signature: &str, tx_idx: usize) -> Self {
+        Self::FundingRate {
+            market_index: value.market_index,
+            record_id: value.record_id,
+            funding_rate: value.funding_rate,
+            funding_rate_long: value.funding_rate_long,
+            funding_rate_short: value.funding_rate_short,
+            oracle_price_twap: value.oracle_price_twap,
+            mark_price_twap: value.mark_price_twap,
+            ts: value.ts.unsigned_abs(),
+            signature: signature.to_string(),
+            tx_idx,
         }
     }
     fn from_funding_payment_record(
@@ -614,6 +1560,8 @@ impl DriftEvent {
                     // TODO: would be nice to report the `user_order_id` too...
                     Some(DriftEvent::OrderExpire {
                         fee: value.filler_reward.unwrap_or_default(),
+                        market_index: value.market_index,
+                        market_type: value.market_type,
                         order_id: value
                             .maker_order_id
                             .or(value.taker_order_id)
@@ -629,6 +1577,8 @@ impl DriftEvent {
                         taker: value.taker,
                         maker_order_id: value.maker_order_id.unwrap_or_default(),
                         taker_order_id: value.taker_order_id.unwrap_or_default(),
+                        market_index: value.market_index,
+                        market_type: value.market_type,
                         ts: value.ts.unsigned_abs(),
                         signature: signature.to_string(),
                         tx_idx,
@@ -661,6 +1611,42 @@ impl DriftEvent {
     }
 }
 
+/// serde as base58 string
+mod serde_pubkey {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use solana_sdk::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer>(value: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            value: &Option<Pubkey>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => serializer.collect_str(value),
+                None => serializer.serialize_none(),
+            }
+        }
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Pubkey>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|s| s.parse().map_err(D::Error::custom))
+                .transpose()
+        }
+    }
+}
+
 /// fixed capacity cache of tx signatures
 struct TxSignatureCache {
     capacity: usize,
@@ -744,9 +1730,12 @@ mod test {
             cache: Arc::new(cache.into()),
             provider: Arc::new(provider),
             endpoint: Arc::new("wss://api.devnet.solana.com".into()),
-            sub_account: "GgZkrSFgTAXZn1rNtZ533wpZi6nxx8whJC9bxRESB22c"
+            account: "GgZkrSFgTAXZn1rNtZ533wpZi6nxx8whJC9bxRESB22c"
                 .try_into()
                 .unwrap(),
+            filter: EventFilter::new().users(["GgZkrSFgTAXZn1rNtZ533wpZi6nxx8whJC9bxRESB22c"
+                .try_into()
+                .unwrap()]),
             event_tx,
             commitment: CommitmentConfig::confirmed(),
         };
@@ -818,9 +1807,10 @@ mod test {
 
         // case 2: jit maker
         // reset the cache and account to process the log from maker's side this time
-        log_stream.sub_account = "5iqawn52cdBmsjC4hDegyFnX1iNRTNDV5mRsGzgqbuyD"
-            .try_into()
-            .unwrap();
+        log_stream.filter =
+            EventFilter::new().users(["5iqawn52cdBmsjC4hDegyFnX1iNRTNDV5mRsGzgqbuyD"
+                .try_into()
+                .unwrap()]);
         log_stream.cache.write().await.reset();
 
         log_stream.process_log(RpcLogsResponse {
@@ -861,6 +1851,167 @@ mod test {
         }
     }
 
+    #[test]
+    fn event_serde_roundtrip() {
+        let log = "Program log: 4DRDR8LtbQFOKvplAAAAAAIIGAABAUYmfxOkiKYWUq/xlZGGa5TzXH6iOR/crh3774xuyUN8AQAAAAAAAAAAAceaAwAAAAAAAQDOav0yAAAAAQQgzQ4AAAAAAQIjAQAAAAAAAQA+////////AAAAAUYmfxOkiKYWUq/xlZGGa5TzXH6iOR/crh3774xuyUN8AZlxNgABAQEAzmr9MgAAAAEAzmr9MgAAAAEEIM0OAAAAAAHpAf4sI0TDV0Ec0LWHs9mO40bjfKEm3A+yye5HFCQQQQEzPgAAAQABANraQssAAAABANraQssAAAABLJgAOwAAAACTWxEAAAAAAA==";
+        let fill = try_parse_log(log, "sig", 0).unwrap().unwrap();
+        let create = DriftEvent::OrderCreate {
+            order: Order {
+                order_id: 7,
+                direction: PositionDirection::Short,
+                ..Default::default()
+            },
+            user: Pubkey::new_unique(),
+            ts: 1,
+            signature: "sig".into(),
+            tx_idx: 1,
+        };
+
+        // enums are serialized by variant name
+        let json = serde_json::to_string(&fill).unwrap();
+        assert!(json.contains(r#""market_type":"Perp""#));
+        assert!(serde_json::from_str::<DriftEvent>(&json.replace("Perp", "Perpetual")).is_err());
+
+        for event in [fill, create] {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<DriftEvent>(&json).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn event_filter() {
+        let cpi_logs = &[
+            "Program log: 4DRDR8LtbQFOKvplAAAAAAAAGAABAAAAAAAAAAAAAAFGJn8TpIimFlKv8ZWRhmuU81x+ojkf3K4d+++MbslDfAGZcTYAAQEBAM5q/TIAAAABAAAAAAAAAAABAAAAAAAAAAAAAAAAAACTWxEAAAAAAA==",
+            "Program log: aBNAOFkVAlpOKvplAAAAAEYmfxOkiKYWUq/xlZGGa5TzXH6iOR/crh3774xuyUN8qZQ2DwAAAABMTREAAAAAAADOav0yAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAJlxNgAYAAEBAQAAAQAAAQAAAAAA",
+            "Program log: 4DRDR8LtbQFOKvplAAAAAAIIGAABAUYmfxOkiKYWUq/xlZGGa5TzXH6iOR/crh3774xuyUN8AQAAAAAAAAAAAceaAwAAAAAAAQDOav0yAAAAAQQgzQ4AAAAAAQIjAQAAAAAAAQA+////////AAAAAUYmfxOkiKYWUq/xlZGGa5TzXH6iOR/crh3774xuyUN8AZlxNgABAQEAzmr9MgAAAAEAzmr9MgAAAAEEIM0OAAAAAAHpAf4sI0TDV0Ec0LWHs9mO40bjfKEm3A+yye5HFCQQQQEzPgAAAQABANraQssAAAABANraQssAAAABLJgAOwAAAACTWxEAAAAAAA==",
+        ];
+        let events: Vec<DriftEvent> = cpi_logs
+            .iter()
+            .filter_map(|log| try_parse_log(log, "sig", 0).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+
+        let count = |filter: EventFilter| events.iter().filter(|e| filter.matches(e)).count();
+        let maker: Pubkey = "GgZkrSFgTAXZn1rNtZ533wpZi6nxx8whJC9bxRESB22c"
+            .try_into()
+            .unwrap();
+
+        assert_eq!(count(EventFilter::new()), 2);
+        assert_eq!(
+            count(EventFilter::new().kinds([DriftEventKind::OrderFill])),
+            1
+        );
+        assert_eq!(count(EventFilter::new().markets([MarketId::perp(24)])), 2);
+        assert_eq!(count(EventFilter::new().markets([MarketId::spot(24)])), 0);
+        assert_eq!(
+            count(
+                EventFilter::new()
+                    .kinds([DriftEventKind::OrderFill])
+                    .users([maker, Pubkey::new_unique()])
+            ),
+            1
+        );
+        assert_eq!(count(EventFilter::new().users([Pubkey::new_unique()])), 0);
+
+        let expire = DriftEvent::OrderExpire {
+            order_id: 1,
+            user: Some(maker),
+            fee: 0,
+            market_index: 24,
+            market_type: MarketType::Spot,
+            ts: 1_710_000_000,
+            signature: "sig".into(),
+            tx_idx: 0,
+        };
+        assert!(EventFilter::new()
+            .markets([MarketId::spot(24)])
+            .matches(&expire));
+        assert!(!EventFilter::new()
+            .markets([MarketId::perp(24)])
+            .matches(&expire));
+
+        // events without market info pass the market predicate
+        let new_user = DriftEvent::NewUser {
+            user: maker,
+            user_authority: Pubkey::new_unique(),
+            sub_account_id: 0,
+            name: [0; 32],
+            referrer: Pubkey::default(),
+            ts: 1_710_000_000,
+            signature: "sig".into(),
+            tx_idx: 0,
+        };
+        assert!(EventFilter::new()
+            .markets([MarketId::spot(24)])
+            .matches(&new_user));
+        assert!(!EventFilter::new()
+            .markets([MarketId::spot(24)])
+            .kinds([DriftEventKind::OrderFill])
+            .matches(&new_user));
+
+        // events without user info only match streams of a single sub-account
+        let cancel_missing = DriftEvent::OrderCancelMissing {
+            user_order_id: 0,
+            order_id: 1,
+            signature: "sig".into(),
+        };
+        assert!(!EventFilter::new().users([maker]).matches(&cancel_missing));
+        assert!(EventFilter::sub_account(maker).matches(&cancel_missing));
+    }
+
+    #[test]
+    fn parses_deposit_record() {
+        let user = Pubkey::new_unique();
+        let log = format!(
+            "{PROGRAM_DATA}{}",
+            serialize_event::<_, 512>(DepositRecord {
+                ts: 1_710_000_000,
+                user,
+                direction: DepositDirection::Withdraw,
+                amount: 1_000_000,
+                market_index: 1,
+                ..Default::default()
+            })
+        );
+
+        let event = try_parse_log(log.as_str(), "sig", 3).unwrap().unwrap();
+        assert!(event.pertains_to(user));
+        assert!(!event.pertains_to(Pubkey::new_unique()));
+        match event {
+            DriftEvent::Deposit {
+                direction,
+                amount,
+                market_index,
+                ts,
+                tx_idx,
+                ..
+            } => {
+                assert_eq!(direction, DepositDirection::Withdraw);
+                assert_eq!(amount, 1_000_000);
+                assert_eq!(market_index, 1);
+                assert_eq!(ts, 1_710_000_000);
+                assert_eq!(tx_idx, 3);
+            }
+            other => panic!("unexpected event: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn truncated_event_is_decode_error() {
+        let mut bytes = OrderRecord::discriminator().to_vec();
+        bytes.extend_from_slice(&[0_u8; 16]);
+        let log = format!("{PROGRAM_LOG}{}", base64::encode(bytes));
+
+        let err = try_parse_log(log.as_str(), "sig", 0).unwrap_err();
+        assert_eq!(err.event, "OrderRecord");
+        assert!(matches!(SdkError::from(err), SdkError::EventDecode(_)));
+
+        // non-event logs are ignored
+        assert!(try_parse_log("Program log: pnl 792986", "sig", 0)
+            .unwrap()
+            .is_none());
+    }
+
     #[tokio::test]
     async fn polled_event_stream_caching() {
         let _ = env_logger::try_init();
@@ -918,6 +2069,15 @@ mod test {
                 }
                 .boxed()
             }
+            fn get_tx_signatures_page(
+                &self,
+                _account: Pubkey,
+                _before: Option<Signature>,
+                _until: Option<Signature>,
+                _limit: Option<usize>,
+            ) -> BoxFuture<SdkResult<Vec<(String, u64)>>> {
+                ready(Ok(vec![])).boxed()
+            }
         }
 
         let (event_tx, mut event_rx) = channel(16);
@@ -997,7 +2157,8 @@ mod test {
             PolledEventStream {
                 cache: Arc::clone(&cache),
                 provider: Arc::clone(&mock_rpc_provider),
-                sub_account,
+                account: sub_account,
+                filter: EventFilter::new().users([sub_account]),
                 event_tx,
             }
             .stream_fn(),
@@ -1038,6 +2199,189 @@ mod test {
         assert!(event_rx.try_recv().is_err());
     }
 
+    #[tokio::test]
+    async fn backfill_and_handoff() {
+        /// txs of account with (signature, slot), newest to oldest
+        struct MockRpcProvider {
+            tx_responses: FnvHashMap<String, EncodedTransactionWithStatusMeta>,
+            signatures: Mutex<Vec<(String, u64)>>,
+            /// tx that can not be fetched
+            failing_tx: Mutex<Option<String>>,
+            /// txs (newest to oldest) landing once signatures until `.0` are requested
+            landing: Mutex<Option<(String, Vec<(String, u64)>)>>,
+        }
+
+        impl EventRpcProvider for Arc<MockRpcProvider> {
+            fn get_tx(
+                &self,
+                signature: Signature,
+            ) -> BoxFuture<SdkResult<EncodedTransactionWithStatusMeta>> {
+                async move {
+                    let signature = signature.to_string();
+                    if self.failing_tx.lock().await.as_ref() == Some(&signature) {
+                        return Err(SdkError::Deserializing);
+                    }
+                    self.tx_responses
+                        .get(signature.as_str())
+                        .ok_or(SdkError::Deserializing)
+                        .cloned()
+                }
+                .boxed()
+            }
+            fn get_tx_signatures(
+                &self,
+                account: Pubkey,
+                after: Option<Signature>,
+                limit: Option<usize>,
+            ) -> BoxFuture<SdkResult<Vec<String>>> {
+                async move {
+                    let page = self
+                        .get_tx_signatures_page(account, None, after, limit)
+                        .await?;
+                    Ok(page.into_iter().map(|(s, _)| s).collect())
+                }
+                .boxed()
+            }
+            fn get_tx_signatures_page(
+                &self,
+                _account: Pubkey,
+                before: Option<Signature>,
+                until: Option<Signature>,
+                limit: Option<usize>,
+            ) -> BoxFuture<SdkResult<Vec<(String, u64)>>> {
+                async move {
+                    let (before, until) =
+                        (before.map(|s| s.to_string()), until.map(|s| s.to_string()));
+                    let mut signatures = self.signatures.lock().await;
+                    let start = before
+                        .and_then(|b| signatures.iter().position(|(s, _)| *s == b))
+                        .map(|idx| idx + 1)
+                        .unwrap_or_default();
+                    // small pages to exercise pagination
+                    let page = signatures[start..]
+                        .iter()
+                        .take_while(|(s, _)| Some(s) != until.as_ref())
+                        .take(limit.unwrap_or(usize::MAX).min(3))
+                        .cloned()
+                        .collect();
+                    let mut landing = self.landing.lock().await;
+                    if landing
+                        .as_ref()
+                        .is_some_and(|(s, _)| Some(s) == until.as_ref())
+                    {
+                        let (_, txs) = landing.take().unwrap();
+                        signatures.splice(0..0, txs);
+                    }
+                    Ok(page)
+                }
+                .boxed()
+            }
+        }
+
+        let sub_account = Pubkey::new_unique();
+        let mut tx_responses = FnvHashMap::<String, EncodedTransactionWithStatusMeta>::default();
+        // order id of each event == slot of its tx
+        let mut signatures: Vec<(String, u64)> = (1..=18)
+            .map(|slot| {
+                let signature = Signature::new_unique();
+                let log = format!(
+                    "{PROGRAM_LOG}{}",
+                    serialize_event::<_, { OrderRecord::SIZE }>(OrderRecord {
+                        ts: slot as i64,
+                        user: sub_account,
+                        order: Order {
+                            order_id: slot as u32,
+                            ..Default::default()
+                        },
+                    })
+                );
+                tx_responses.insert(
+                    signature.to_string(),
+                    make_transaction(sub_account, signature, Some(vec![log])),
+                );
+                (signature.to_string(), slot)
+            })
+            .rev()
+            .collect();
+        let landing_txs: Vec<(String, u64)> = signatures.drain(..7).collect();
+        let live_tx = signatures.remove(0);
+        let mock_rpc_provider = Arc::new(MockRpcProvider {
+            tx_responses,
+            signatures: Mutex::new(signatures.clone()),
+            failing_tx: Mutex::default(),
+            landing: Mutex::default(),
+        });
+
+        async fn order_ids(stream: &mut DriftEventStream, n: usize) -> Vec<u32> {
+            let mut order_ids = vec![];
+            for _ in 0..n {
+                match tokio::time::timeout(Duration::from_secs(2), stream.next()).await {
+                    Ok(Some(DriftEvent::OrderCreate { order, .. })) => {
+                        order_ids.push(order.order_id)
+                    }
+                    _ => break,
+                }
+            }
+            order_ids
+        }
+
+        // bounded range by slot, the stream ends
+        let mut stream = EventSubscriber::backfill(
+            Arc::clone(&mock_rpc_provider),
+            sub_account,
+            TxCursor::Slot(3),
+            Some(TxCursor::Slot(7)),
+        );
+        assert_eq!(order_ids(&mut stream, 10).await, vec![3, 4, 5, 6, 7]);
+
+        // resume from signature and hand-off to live polling
+        let slot_8_signature = Signature::from_str(&signatures[2].0).unwrap();
+        let mut stream = EventSubscriber::backfill(
+            Arc::clone(&mock_rpc_provider),
+            sub_account,
+            TxCursor::Signature(slot_8_signature),
+            None,
+        );
+        assert_eq!(order_ids(&mut stream, 2).await, vec![9, 10]);
+        mock_rpc_provider.signatures.lock().await.insert(0, live_tx);
+        assert_eq!(order_ids(&mut stream, 2).await, vec![11]);
+
+        // txs landing during the backfill span several pages, none are skipped in the hand-off
+        let slot_10_signature = signatures[0].0.clone();
+        *mock_rpc_provider.landing.lock().await = Some((slot_10_signature.clone(), landing_txs));
+        let mut stream = EventSubscriber::backfill(
+            Arc::clone(&mock_rpc_provider),
+            sub_account,
+            TxCursor::Signature(Signature::from_str(&slot_10_signature).unwrap()),
+            None,
+        );
+        assert_eq!(
+            order_ids(&mut stream, 9).await,
+            (11..=18).collect::<Vec<u32>>()
+        );
+
+        // a tx that can not be fetched ends the stream rather than leave a gap
+        let slot_5_signature = signatures.iter().find(|(_, slot)| *slot == 5).unwrap();
+        *mock_rpc_provider.failing_tx.lock().await = Some(slot_5_signature.0.clone());
+        let stream = EventSubscriber::backfill(
+            Arc::clone(&mock_rpc_provider),
+            sub_account,
+            TxCursor::Slot(3),
+            None,
+        );
+        let order_ids: Vec<u32> =
+            tokio::time::timeout(Duration::from_secs(10), stream.collect::<Vec<DriftEvent>>())
+                .await
+                .expect("stream ends")
+                .into_iter()
+                .filter_map(|event| match event {
+                    DriftEvent::OrderCreate { order, .. } => Some(order.order_id),
+                    _ => None,
+                })
+                .collect();
+        assert_eq!(order_ids, vec![3, 4]);
+    }
+
     /// Make transaction with dummy instruction for drift program
     fn make_transaction(
         account: Pubkey,
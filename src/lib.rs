@@ -27,7 +27,7 @@ use oraclemap::{Oracle, OracleMap};
 use solana_account_decoder::UiAccountEncoding;
 use solana_client::{
     nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
-    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionConfig},
     rpc_filter::{Memcmp, RpcFilterType},
 };
 use solana_sdk::{
@@ -39,11 +39,14 @@ use solana_sdk::{
     hash::Hash,
     instruction::{AccountMeta, Instruction},
     message::{v0, Message, VersionedMessage},
+    rent::Rent,
     signature::{keypair_from_seed, Keypair, Signature},
-    signer::Signer,
+    signer::{Signer, SignerError},
+    system_instruction, system_program, sysvar,
     transaction::VersionedTransaction,
 };
 pub use solana_sdk::{address_lookup_table_account::AddressLookupTableAccount, pubkey::Pubkey};
+use spl_token::instruction::{close_account, initialize_account3};
 use tokio::{
     select,
     sync::{
@@ -74,13 +77,16 @@ pub mod types;
 
 // internal infra
 pub mod event_emitter;
+mod serde_helpers;
 pub mod websocket_account_subscriber;
 pub mod websocket_program_account_subscriber;
 
 // subscribers
 pub mod auction_subscriber;
 pub mod blockhash_subscriber;
+pub mod candles;
 pub mod dlob_client;
+pub mod event_sink;
 pub mod event_subscriber;
 
 #[cfg(feature = "jit")]
@@ -92,6 +98,7 @@ pub mod slot_subscriber;
 pub mod usermap;
 
 // wrappers
+pub mod signer;
 pub mod user;
 
 pub mod dlob;
@@ -551,6 +558,83 @@ impl<T: AccountProvider> DriftClient<T> {
             .map_err(|err| err.to_out_of_sol_error().unwrap_or(err))
     }
 
+    /// Sign and send a tx to the network, waiting until it lands
+    ///
+    /// The tx is rebroadcast every `config.rebroadcast_interval` until it is confirmed at
+    /// `config.commitment`, its blockhash expires, or `config.timeout` elapses.
+    /// On expiry it is re-signed with a fresh blockhash up to `config.max_resigns` times.
+    ///
+    /// ```ignore
+    /// let (signature, outcome) = client.sign_and_confirm(tx, SendConfig::default()).await?;
+    /// match outcome {
+    ///     TxOutcome::Landed { slot, err: None } => println!("landed at {slot}: {signature:?}"),
+    ///     TxOutcome::Landed { err: Some(err), .. } => println!("tx failed: {err:?}"),
+    ///     TxOutcome::Expired | TxOutcome::Dropped => println!("tx did not land"),
+    /// }
+    /// ```
+    /// Returns the (final) tx signature and outcome
+    pub async fn sign_and_confirm(
+        &self,
+        tx: VersionedMessage,
+        config: SendConfig,
+    ) -> SdkResult<(Signature, TxOutcome)> {
+        self.backend
+            .sign_and_confirm(self.wallet(), tx, config)
+            .await
+            .map_err(|err| err.to_out_of_sol_error().unwrap_or(err))
+    }
+
+    /// Simulate a tx against the current cluster state, without signing or paying fees
+    ///
+    /// ```ignore
+    /// let result = client.simulate(tx).await?;
+    /// if let Some(code) = result.error_code {
+    ///     println!("order would fail: {code:?}");
+    /// }
+    /// ```
+    /// Returns the `SimulationResult` (compute units, drift events, program error) on success
+    pub async fn simulate(&self, tx: VersionedMessage) -> SdkResult<SimulationResult> {
+        self.backend.simulate(tx).await
+    }
+
+    /// Create a durable nonce account owned by the wallet authority
+    ///
+    /// The account address is derived from the authority and `seed`, see `Wallet::derive_nonce_account`
+    ///
+    /// Returns the nonce account address and tx signature on success
+    pub async fn create_nonce_account(&self, seed: &str) -> SdkResult<(Pubkey, Signature)> {
+        let authority = self.wallet.authority();
+        let nonce_account = Wallet::derive_nonce_account(authority, seed)?;
+        let lamports = self
+            .backend
+            .client()
+            .get_minimum_balance_for_rent_exemption(solana_sdk::nonce::State::size())
+            .await?;
+        let ixs = system_instruction::create_nonce_account_with_seed(
+            authority,
+            &nonce_account,
+            authority,
+            seed,
+            authority,
+            lamports,
+        );
+        let tx = VersionedMessage::Legacy(Message::new(ixs.as_slice(), Some(authority)));
+        let signature = self.sign_and_send(tx).await?;
+
+        Ok((nonce_account, signature))
+    }
+
+    /// Get the current nonce value of a durable nonce account
+    ///
+    /// Returns the nonce for use as a tx blockhash e.g. with `TransactionBuilder::with_durable_nonce`
+    pub async fn get_nonce(&self, nonce_account: &Pubkey) -> SdkResult<Hash> {
+        let account = self.backend.client().get_account(nonce_account).await?;
+        let data = solana_client::nonce_utils::data_from_account(&account)
+            .map_err(|_| SdkError::InvalidAccount)?;
+
+        Ok(data.blockhash())
+    }
+
     /// Get live info of a spot market
     pub async fn get_spot_market_info(&self, market_index: u16) -> SdkResult<SpotMarket> {
         let market = derive_spot_market_account(market_index);
@@ -622,6 +706,80 @@ impl<T: AccountProvider> DriftClient<T> {
         }
     }
 
+    /// Initialize a transaction for a sub-account that does not exist on-chain yet
+    ///
+    /// ```ignore
+    /// let tx = client
+    ///     .init_new_account_tx(1)
+    ///     .initialize_user("strategy-1", None)
+    ///     .update_user_delegate(delegate)
+    ///     .build();
+    /// ```
+    /// Returns a `TransactionBuilder` for composing the tx
+    pub fn init_new_account_tx(&self, sub_account_id: u16) -> TransactionBuilder {
+        let authority = *self.wallet.authority();
+        TransactionBuilder::new(
+            self.program_data(),
+            self.wallet.sub_account(sub_account_id),
+            Cow::Owned(User {
+                authority,
+                sub_account_id,
+                ..Default::default()
+            }),
+            false,
+        )
+    }
+
+    /// Initialize a transaction transferring a spot deposit between two sub-accounts of the wallet
+    ///
+    /// ```ignore
+    /// let tx = client
+    ///     .transfer_deposit_tx(0, 1, MarketId::QUOTE_SPOT.index, 100 * QUOTE_PRECISION)
+    ///     .await?
+    ///     .build();
+    /// ```
+    /// Returns a `TransactionBuilder` for composing the tx or error if either sub-account does not exist
+    pub async fn transfer_deposit_tx(
+        &self,
+        from_sub_account: u16,
+        to_sub_account: u16,
+        market_index: u16,
+        amount: u64,
+    ) -> SdkResult<TransactionBuilder> {
+        if from_sub_account == to_sub_account {
+            return Err(SdkError::Generic(
+                "cannot transfer to the same sub-account".to_string(),
+            ));
+        }
+
+        let from = self.wallet.sub_account(from_sub_account);
+        let to = self.wallet.sub_account(to_sub_account);
+        // only a missing account means the sub-account does not exist
+        let no_sub_account = |sub_account_id: u16| {
+            move |err: SdkError| {
+                if err.is_account_not_found() {
+                    SdkError::NoSubAccount(sub_account_id)
+                } else {
+                    err
+                }
+            }
+        };
+        let (from_account, to_account) = tokio::try_join!(
+            self.get_user_account(&from)
+                .map_err(no_sub_account(from_sub_account)),
+            self.get_user_account(&to)
+                .map_err(no_sub_account(to_sub_account)),
+        )?;
+
+        Ok(TransactionBuilder::new(
+            self.program_data(),
+            from,
+            Cow::Owned(from_account),
+            self.wallet.is_delegated(),
+        )
+        .transfer_deposit(&(to, to_account), market_index, amount))
+    }
+
     pub async fn get_recent_priority_fees(
         &self,
         writable_markets: &[MarketId],
@@ -958,6 +1116,17 @@ impl<T: AccountProvider> DriftClientBackend<T> {
         U::try_deserialize(&mut account_data.data.as_ref()).map_err(|_err| SdkError::InvalidAccount)
     }
 
+    /// Return the blockhash to sign `tx` with and its last valid block height
+    ///
+    /// durable nonce txs keep their nonce and never expire
+    async fn recent_blockhash(&self, tx: &VersionedMessage) -> (Hash, u64) {
+        if utils::uses_durable_nonce(tx) {
+            return (*tx.recent_blockhash(), u64::MAX);
+        }
+        let blockhash_reader = self.blockhash_subscriber.read().await;
+        blockhash_reader.get_valid_blockhash_with_height()
+    }
+
     /// Sign and send a tx to the network
     ///
     /// Returns the signature on success
@@ -966,10 +1135,8 @@ impl<T: AccountProvider> DriftClientBackend<T> {
         wallet: &Wallet,
         tx: VersionedMessage,
     ) -> SdkResult<Signature> {
-        let blockhash_reader = self.blockhash_subscriber.read().await;
-        let recent_block_hash = blockhash_reader.get_valid_blockhash();
-        drop(blockhash_reader);
-        let tx = wallet.sign_tx(tx, recent_block_hash)?;
+        let (recent_block_hash, _) = self.recent_blockhash(&tx).await;
+        let tx = wallet.sign_tx(tx, recent_block_hash).await?;
         self.rpc_client
             .send_transaction(&tx)
             .await
@@ -986,16 +1153,118 @@ impl<T: AccountProvider> DriftClientBackend<T> {
         tx: VersionedMessage,
         config: RpcSendTransactionConfig,
     ) -> SdkResult<Signature> {
-        let blockhash_reader = self.blockhash_subscriber.read().await;
-        let recent_block_hash = blockhash_reader.get_valid_blockhash();
-        drop(blockhash_reader);
-        let tx = wallet.sign_tx(tx, recent_block_hash)?;
+        let (recent_block_hash, _) = self.recent_blockhash(&tx).await;
+        let tx = wallet.sign_tx(tx, recent_block_hash).await?;
         self.rpc_client
             .send_transaction_with_config(&tx, config)
             .await
             .map_err(|err| err.into())
     }
 
+    /// Sign and send a tx, rebroadcasting it until it lands or its blockhash expires
+    ///
+    /// Returns the final tx signature and its outcome
+    pub async fn sign_and_confirm(
+        &self,
+        wallet: &Wallet,
+        tx: VersionedMessage,
+        config: SendConfig,
+    ) -> SdkResult<(Signature, TxOutcome)> {
+        let (recent_block_hash, mut last_valid_block_height) = self.recent_blockhash(&tx).await;
+        let mut signed_tx = wallet.sign_tx(tx.clone(), recent_block_hash).await?;
+        // first send is preflighted to surface errors early
+        let mut signature = self.rpc_client.send_transaction(&signed_tx).await?;
+
+        let rebroadcast_config = RpcSendTransactionConfig {
+            skip_preflight: true,
+            max_retries: Some(0),
+            ..Default::default()
+        };
+        let started = std::time::Instant::now();
+        let mut resigns = 0;
+        loop {
+            tokio::time::sleep(config.rebroadcast_interval).await;
+
+            let status = self
+                .rpc_client
+                .get_signature_statuses(&[signature])
+                .await?
+                .value
+                .pop()
+                .flatten();
+            if let Some(status) = &status {
+                if status.satisfies_commitment(config.commitment) {
+                    let outcome = TxOutcome::Landed {
+                        slot: status.slot,
+                        err: status.err.clone(),
+                    };
+                    return Ok((signature, outcome));
+                }
+            }
+
+            if started.elapsed() > config.timeout {
+                return Ok((signature, TxOutcome::Dropped));
+            }
+
+            if status.is_some() {
+                // seen but not yet at the desired commitment, the tx can no longer expire
+                // and a re-signed copy could land alongside it
+                continue;
+            }
+
+            let block_height = self
+                .rpc_client
+                .get_block_height_with_commitment(self.rpc_client.commitment())
+                .await?;
+            if block_height > last_valid_block_height {
+                if resigns >= config.max_resigns {
+                    return Ok((signature, TxOutcome::Expired));
+                }
+                resigns += 1;
+                let (blockhash, height) = self
+                    .rpc_client
+                    .get_latest_blockhash_with_commitment(self.rpc_client.commitment())
+                    .await?;
+                debug!("tx expired: {signature:?}, re-signing ({resigns})");
+                last_valid_block_height = height;
+                signed_tx = wallet.sign_tx(tx.clone(), blockhash).await?;
+                signature = signed_tx.signatures[0];
+            }
+
+            if let Err(err) = self
+                .rpc_client
+                .send_transaction_with_config(&signed_tx, rebroadcast_config)
+                .await
+            {
+                warn!("tx rebroadcast failed: {signature:?}, {err:?}");
+            }
+        }
+    }
+
+    /// Simulate a tx against the current cluster state
+    ///
+    /// Signature verification is skipped and the blockhash replaced so the tx need not be signed
+    pub async fn simulate(&self, tx: VersionedMessage) -> SdkResult<SimulationResult> {
+        let tx = VersionedTransaction {
+            signatures: vec![Signature::default(); tx.header().num_required_signatures as usize],
+            message: tx,
+        };
+        let response = self
+            .rpc_client
+            .simulate_transaction_with_config(
+                &tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(self.rpc_client.commitment()),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        Ok(SimulationResult::new(response.value, &tx))
+    }
+
     /// Fetch the live oracle price for `market`
     pub async fn oracle_price(&self, market: MarketId) -> SdkResult<i64> {
         let (oracle, oracle_source) = match market.kind {
@@ -1067,6 +1336,8 @@ pub struct TransactionBuilder<'a> {
     legacy: bool,
     /// add additional lookup tables (v0 only)
     lookup_tables: Vec<AddressLookupTableAccount>,
+    /// durable nonce account and its current nonce value
+    nonce: Option<(Pubkey, Hash)>,
 }
 
 impl<'a> TransactionBuilder<'a> {
@@ -1097,6 +1368,7 @@ impl<'a> TransactionBuilder<'a> {
             ixs: Default::default(),
             lookup_tables: vec![program_data.lookup_table.clone()],
             legacy: false,
+            nonce: None,
         }
     }
     /// Use legacy tx mode
@@ -1112,6 +1384,18 @@ impl<'a> TransactionBuilder<'a> {
 
         self
     }
+    /// Use a durable nonce instead of a recent blockhash for the tx
+    ///
+    /// An `advance_nonce_account` ix is prepended to the tx and `nonce` set as its blockhash,
+    /// the tx authority must be the nonce authority.
+    ///
+    /// `nonce_account` address of the nonce account
+    ///
+    /// `nonce` current nonce value e.g. from `DriftClient::get_nonce`
+    pub fn with_durable_nonce(mut self, nonce_account: Pubkey, nonce: Hash) -> Self {
+        self.nonce = Some((nonce_account, nonce));
+        self
+    }
     /// Set the priority fee of the tx
     ///
     /// `microlamports_per_cu` the price per unit of compute in µ-lamports
@@ -1204,6 +1488,101 @@ impl<'a> TransactionBuilder<'a> {
         self
     }
 
+    /// Transfer a spot deposit from the tx sub-account to another sub-account of the same authority
+    ///
+    /// `to_sub_account` address and account data of the receiving sub-account
+    ///
+    /// `market_index` the spot market of the deposit
+    ///
+    /// `amount` the amount to transfer in token precision
+    pub fn transfer_deposit(
+        mut self,
+        to_sub_account: &(Pubkey, User),
+        market_index: u16,
+        amount: u64,
+    ) -> Self {
+        let (to_user, to_account) = to_sub_account;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::TransferDeposit {
+                from_user: self.sub_account,
+                to_user: *to_user,
+                user_stats: Wallet::derive_stats_account(&self.authority, &constants::PROGRAM_ID),
+                authority: self.authority,
+                state: *state_account(),
+                spot_market_vault: constants::derive_spot_market_vault(market_index),
+            },
+            &[self.account_data.as_ref(), to_account],
+            &[],
+            &[MarketId::spot(market_index)],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::TransferDeposit {
+                market_index,
+                amount,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Deposit native SOL into the SOL spot market
+    ///
+    /// The SOL is wrapped via a temporary wSOL token account which is closed again in the same tx
+    ///
+    /// # Errors
+    ///  if there is no SOL spot market
+    pub fn deposit_sol(mut self, amount: u64, reduce_only: Option<bool>) -> SdkResult<Self> {
+        let market_index = self.sol_spot_market_index()?;
+
+        let (wsol_account, wsol_ixs) = temporary_wsol_account(&self.authority, amount)?;
+        self.ixs.extend(wsol_ixs);
+        self = self.deposit(amount, market_index, wsol_account, reduce_only);
+        self.ixs.push(close_account(
+            &spl_token::ID,
+            &wsol_account,
+            &self.authority,
+            &self.authority,
+            &[],
+        )?);
+
+        Ok(self)
+    }
+
+    /// Withdraw native SOL from the SOL spot market
+    ///
+    /// The SOL is unwrapped via a temporary wSOL token account which is closed again in the same tx
+    ///
+    /// # Errors
+    ///  if there is no SOL spot market
+    pub fn withdraw_sol(mut self, amount: u64, reduce_only: Option<bool>) -> SdkResult<Self> {
+        let market_index = self.sol_spot_market_index()?;
+
+        let (wsol_account, wsol_ixs) = temporary_wsol_account(&self.authority, 0)?;
+        self.ixs.extend(wsol_ixs);
+        self = self.withdraw(amount, market_index, wsol_account, reduce_only);
+        self.ixs.push(close_account(
+            &spl_token::ID,
+            &wsol_account,
+            &self.authority,
+            &self.authority,
+            &[],
+        )?);
+
+        Ok(self)
+    }
+
+    fn sol_spot_market_index(&self) -> SdkResult<u16> {
+        self.program_data
+            .sol_spot_market_config()
+            .map(|market| market.market_index)
+            .ok_or_else(|| SdkError::Generic("no SOL spot market".to_string()))
+    }
+
     /// Place new orders for account
     pub fn place_orders(mut self, orders: Vec<OrderParams>) -> Self {
         let readable_accounts: Vec<MarketId> = orders
@@ -1556,106 +1935,950 @@ impl<'a> TransactionBuilder<'a> {
         self
     }
 
-    /// Build the transaction message ready for signing and sending
-    pub fn build(self) -> VersionedMessage {
-        if self.legacy {
-            let message = Message::new(self.ixs.as_ref(), Some(&self.authority));
-            VersionedMessage::Legacy(message)
-        } else {
-            let message = v0::Message::try_compile(
-                &self.authority,
-                self.ixs.as_slice(),
-                self.lookup_tables.as_slice(),
-                Default::default(),
-            )
-            .expect("ok");
-            VersionedMessage::V0(message)
-        }
-    }
-
-    pub fn program_data(&self) -> &ProgramData {
-        self.program_data
-    }
-
-    pub fn account_data(&self) -> &Cow<'_, User> {
-        &self.account_data
-    }
-}
-
-/// Builds a set of required accounts from a user's open positions and additional given accounts
-///
-/// `base_accounts` base anchor accounts
-///
-/// `user` Drift user account data
-///
-/// `markets_readable` IDs of markets to include as readable
-///
-/// `markets_writable` IDs of markets to include as writable (takes priority over readable)
-///
-/// # Panics
-///  if the user has positions in an unknown market (i.e unsupported by the SDK)
-pub fn build_accounts(
-    program_data: &ProgramData,
-    base_accounts: impl ToAccountMetas,
-    users: &[&User],
-    markets_readable: &[MarketId],
-    markets_writable: &[MarketId],
-) -> Vec<AccountMeta> {
-    // the order of accounts returned must be instruction, oracles, spot, perps see (https://github.com/drift-labs/protocol-v2/blob/master/programs/drift/src/instructions/optional_accounts.rs#L28)
-    let mut seen = [0_u64; 2]; // [spot, perp]
-    let mut accounts = Vec::<RemainingAccount>::default();
+    /// Settle PnL of a perp market position
+    ///
+    /// `market_index` the perp market to settle
+    ///
+    /// `settlee_info` address and account data of the user to settle, defaults to the tx sub-account
+    pub fn settle_pnl(mut self, market_index: u16, settlee_info: Option<&(Pubkey, User)>) -> Self {
+        let (settlee, settlee_account) = match settlee_info {
+            Some((pubkey, user)) => (*pubkey, user),
+            None => (self.sub_account, self.account_data.as_ref()),
+        };
 
-    // add accounts to the ordered list
-    let mut include_market = |market_index: u16, market_type: MarketType, writable: bool| {
-        let index_bit = 1_u64 << market_index as u8;
-        // always safe since market type is 0 or 1
-        let seen_by_type = unsafe { seen.get_unchecked_mut(market_type as usize % 2) };
-        if *seen_by_type & index_bit > 0 {
-            return;
-        }
-        *seen_by_type |= index_bit;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::SettlePNL {
+                state: *state_account(),
+                authority: self.authority,
+                user: settlee,
+                spot_market_vault: constants::derive_spot_market_vault(MarketId::QUOTE_SPOT.index),
+            },
+            &[settlee_account],
+            &[],
+            &[MarketId::perp(market_index), MarketId::QUOTE_SPOT],
+        );
 
-        let (account, oracle) = match market_type {
-            MarketType::Spot => {
-                let SpotMarket { pubkey, oracle, .. } = program_data
-                    .spot_market_config_by_index(market_index)
-                    .expect("exists");
-                (
-                    RemainingAccount::Spot {
-                        pubkey: *pubkey,
-                        writable,
-                    },
-                    oracle,
-                )
-            }
-            MarketType::Perp => {
-                let PerpMarket { pubkey, amm, .. } = program_data
-                    .perp_market_config_by_index(market_index)
-                    .expect("exists");
-                (
-                    RemainingAccount::Perp {
-                        pubkey: *pubkey,
-                        writable,
-                    },
-                    &amm.oracle,
-                )
-            }
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::SettlePnl { market_index }),
         };
-        if let Err(idx) = accounts.binary_search(&account) {
-            accounts.insert(idx, account);
-        }
-        let oracle = RemainingAccount::Oracle { pubkey: *oracle };
-        if let Err(idx) = accounts.binary_search(&oracle) {
-            accounts.insert(idx, oracle);
-        }
-    };
 
-    for MarketId { index, kind } in markets_writable {
-        include_market(*index, *kind, true);
-    }
-
-    for MarketId { index, kind } in markets_readable {
-        include_market(*index, *kind, false);
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Settle PnL of multiple perp market positions in one instruction
+    ///
+    /// `market_indexes` the perp markets to settle
+    ///
+    /// `mode` fail the tx if any market cannot be settled or try settle as many as possible
+    ///
+    /// `settlee_info` address and account data of the user to settle, defaults to the tx sub-account
+    pub fn settle_multiple_pnls(
+        mut self,
+        market_indexes: &[u16],
+        mode: SettlePnlMode,
+        settlee_info: Option<&(Pubkey, User)>,
+    ) -> Self {
+        let (settlee, settlee_account) = match settlee_info {
+            Some((pubkey, user)) => (*pubkey, user),
+            None => (self.sub_account, self.account_data.as_ref()),
+        };
+
+        let mut writable_markets: Vec<MarketId> = market_indexes
+            .iter()
+            .map(|idx| MarketId::perp(*idx))
+            .collect();
+        writable_markets.push(MarketId::QUOTE_SPOT);
+
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::SettlePNL {
+                state: *state_account(),
+                authority: self.authority,
+                user: settlee,
+                spot_market_vault: constants::derive_spot_market_vault(MarketId::QUOTE_SPOT.index),
+            },
+            &[settlee_account],
+            &[],
+            writable_markets.as_slice(),
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::SettleMultiplePnls {
+                market_indexes: market_indexes.to_vec(),
+                mode,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Liquidate a perp position of `liquidatee`, taking over its base amount
+    ///
+    /// `market_index` the perp market to liquidate
+    ///
+    /// `liquidatee_info` address and account data of the user being liquidated
+    ///
+    /// `max_base_asset_amount` max. base amount for the liquidator to take over
+    ///
+    /// `limit_price` worst acceptable price for the liquidator, if any
+    pub fn liquidate_perp(
+        mut self,
+        market_index: u16,
+        liquidatee_info: &(Pubkey, User),
+        max_base_asset_amount: u64,
+        limit_price: Option<u64>,
+    ) -> Self {
+        let (liquidatee, liquidatee_account) = liquidatee_info;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::LiquidatePerp {
+                state: *state_account(),
+                authority: self.authority,
+                liquidator: self.sub_account,
+                liquidator_stats: Wallet::derive_stats_account(
+                    &self.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                user: *liquidatee,
+                user_stats: Wallet::derive_stats_account(
+                    &liquidatee_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+            },
+            &[liquidatee_account, self.account_data.as_ref()],
+            &[],
+            &[MarketId::perp(market_index)],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::LiquidatePerp {
+                market_index,
+                liquidator_max_base_asset_amount: max_base_asset_amount,
+                limit_price,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Liquidate a spot borrow of `liquidatee` in exchange for one of its spot deposits
+    ///
+    /// `asset_market_index` the spot market of the deposit received by the liquidator
+    ///
+    /// `liability_market_index` the spot market of the borrow taken over by the liquidator
+    ///
+    /// `liquidatee_info` address and account data of the user being liquidated
+    ///
+    /// `max_liability_transfer` max. liability amount for the liquidator to take over
+    ///
+    /// `limit_price` worst acceptable price for the liquidator, if any
+    pub fn liquidate_spot(
+        mut self,
+        asset_market_index: u16,
+        liability_market_index: u16,
+        liquidatee_info: &(Pubkey, User),
+        max_liability_transfer: u128,
+        limit_price: Option<u64>,
+    ) -> Self {
+        let (liquidatee, liquidatee_account) = liquidatee_info;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::LiquidateSpot {
+                state: *state_account(),
+                authority: self.authority,
+                liquidator: self.sub_account,
+                liquidator_stats: Wallet::derive_stats_account(
+                    &self.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                user: *liquidatee,
+                user_stats: Wallet::derive_stats_account(
+                    &liquidatee_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+            },
+            &[liquidatee_account, self.account_data.as_ref()],
+            &[],
+            &[
+                MarketId::spot(liability_market_index),
+                MarketId::spot(asset_market_index),
+            ],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::LiquidateSpot {
+                asset_market_index,
+                liability_market_index,
+                liquidator_max_liability_transfer: max_liability_transfer,
+                limit_price,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Liquidate a spot borrow of `liquidatee` in exchange for its positive perp PnL
+    ///
+    /// `perp_market_index` the perp market with the PnL received by the liquidator
+    ///
+    /// `liability_market_index` the spot market of the borrow taken over by the liquidator
+    ///
+    /// `liquidatee_info` address and account data of the user being liquidated
+    ///
+    /// `max_liability_transfer` max. liability amount for the liquidator to take over
+    ///
+    /// `limit_price` worst acceptable price for the liquidator, if any
+    pub fn liquidate_borrow_for_perp_pnl(
+        mut self,
+        perp_market_index: u16,
+        liability_market_index: u16,
+        liquidatee_info: &(Pubkey, User),
+        max_liability_transfer: u128,
+        limit_price: Option<u64>,
+    ) -> Self {
+        let (liquidatee, liquidatee_account) = liquidatee_info;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::LiquidateBorrowForPerpPnl {
+                state: *state_account(),
+                authority: self.authority,
+                liquidator: self.sub_account,
+                liquidator_stats: Wallet::derive_stats_account(
+                    &self.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                user: *liquidatee,
+                user_stats: Wallet::derive_stats_account(
+                    &liquidatee_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+            },
+            &[liquidatee_account, self.account_data.as_ref()],
+            &[],
+            &[
+                MarketId::perp(perp_market_index),
+                MarketId::spot(liability_market_index),
+            ],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::LiquidateBorrowForPerpPnl {
+                perp_market_index,
+                spot_market_index: liability_market_index,
+                liquidator_max_liability_transfer: max_liability_transfer,
+                limit_price,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Liquidate negative perp PnL of `liquidatee` in exchange for one of its spot deposits
+    ///
+    /// `perp_market_index` the perp market with the PnL taken over by the liquidator
+    ///
+    /// `asset_market_index` the spot market of the deposit received by the liquidator
+    ///
+    /// `liquidatee_info` address and account data of the user being liquidated
+    ///
+    /// `max_pnl_transfer` max. PnL amount for the liquidator to take over
+    ///
+    /// `limit_price` worst acceptable price for the liquidator, if any
+    pub fn liquidate_perp_pnl_for_deposit(
+        mut self,
+        perp_market_index: u16,
+        asset_market_index: u16,
+        liquidatee_info: &(Pubkey, User),
+        max_pnl_transfer: u128,
+        limit_price: Option<u64>,
+    ) -> Self {
+        let (liquidatee, liquidatee_account) = liquidatee_info;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::LiquidatePerpPnlForDeposit {
+                state: *state_account(),
+                authority: self.authority,
+                liquidator: self.sub_account,
+                liquidator_stats: Wallet::derive_stats_account(
+                    &self.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                user: *liquidatee,
+                user_stats: Wallet::derive_stats_account(
+                    &liquidatee_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+            },
+            &[liquidatee_account, self.account_data.as_ref()],
+            &[],
+            &[
+                MarketId::perp(perp_market_index),
+                MarketId::spot(asset_market_index),
+            ],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::LiquidatePerpPnlForDeposit {
+                perp_market_index,
+                spot_market_index: asset_market_index,
+                liquidator_max_pnl_transfer: max_pnl_transfer,
+                limit_price,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Resolve a bankrupt perp position of `liquidatee`, socializing its loss via the insurance fund
+    ///
+    /// `market_index` the perp market to resolve
+    ///
+    /// `liquidatee_info` address and account data of the bankrupt user
+    pub fn resolve_perp_bankruptcy(
+        mut self,
+        market_index: u16,
+        liquidatee_info: &(Pubkey, User),
+    ) -> Self {
+        let (liquidatee, liquidatee_account) = liquidatee_info;
+        let quote_market_index = MarketId::QUOTE_SPOT.index;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::ResolveBankruptcy {
+                state: *state_account(),
+                authority: self.authority,
+                liquidator: self.sub_account,
+                liquidator_stats: Wallet::derive_stats_account(
+                    &self.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                user: *liquidatee,
+                user_stats: Wallet::derive_stats_account(
+                    &liquidatee_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                spot_market_vault: constants::derive_spot_market_vault(quote_market_index),
+                insurance_fund_vault: constants::derive_insurance_fund_vault(quote_market_index),
+                drift_signer: constants::derive_drift_signer(),
+                token_program: constants::TOKEN_PROGRAM_ID,
+            },
+            &[liquidatee_account, self.account_data.as_ref()],
+            &[],
+            &[MarketId::perp(market_index), MarketId::QUOTE_SPOT],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::ResolvePerpBankruptcy {
+                quote_spot_market_index: quote_market_index,
+                market_index,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Resolve a bankrupt spot borrow of `liquidatee`, socializing its loss via the insurance fund
+    ///
+    /// `market_index` the spot market to resolve
+    ///
+    /// `liquidatee_info` address and account data of the bankrupt user
+    pub fn resolve_spot_bankruptcy(
+        mut self,
+        market_index: u16,
+        liquidatee_info: &(Pubkey, User),
+    ) -> Self {
+        let (liquidatee, liquidatee_account) = liquidatee_info;
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::ResolveBankruptcy {
+                state: *state_account(),
+                authority: self.authority,
+                liquidator: self.sub_account,
+                liquidator_stats: Wallet::derive_stats_account(
+                    &self.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                user: *liquidatee,
+                user_stats: Wallet::derive_stats_account(
+                    &liquidatee_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+                spot_market_vault: constants::derive_spot_market_vault(market_index),
+                insurance_fund_vault: constants::derive_insurance_fund_vault(market_index),
+                drift_signer: constants::derive_drift_signer(),
+                token_program: constants::TOKEN_PROGRAM_ID,
+            },
+            &[liquidatee_account, self.account_data.as_ref()],
+            &[],
+            &[MarketId::spot(market_index)],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::ResolveSpotBankruptcy {
+                market_index,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Fill a perp order of `taker`, against the given makers and/or the vAMM
+    ///
+    /// `taker_info` address and account data of the order owner
+    ///
+    /// `taker_order` the order to fill e.g. from a DLOB node
+    ///
+    /// `makers` address and account data of makers to fill against e.g. from resting DLOB nodes
+    ///
+    /// `referrer` the taker's referrer, if any
+    pub fn fill_perp_order(
+        mut self,
+        taker_info: &(Pubkey, User),
+        taker_order: &Order,
+        makers: &[(Pubkey, User)],
+        referrer: Option<ReferrerInfo>,
+    ) -> Self {
+        let (taker, taker_account) = taker_info;
+        let market_index = taker_order.market_index;
+
+        let mut user_accounts = vec![taker_account];
+        user_accounts.extend(makers.iter().map(|(_, maker)| maker));
+
+        let mut accounts = build_accounts(
+            self.program_data,
+            drift::accounts::FillOrder {
+                state: *state_account(),
+                authority: self.authority,
+                filler: self.sub_account,
+                filler_stats: Wallet::derive_stats_account(&self.authority, &constants::PROGRAM_ID),
+                user: *taker,
+                user_stats: Wallet::derive_stats_account(
+                    &taker_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+            },
+            user_accounts.as_slice(),
+            &[],
+            &[MarketId::perp(market_index)],
+        );
+        accounts.extend(maker_and_referrer_accounts(makers, referrer));
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::FillPerpOrder {
+                order_id: Some(taker_order.order_id),
+                _maker_order_id: None,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Fill a spot order of `taker` against the given makers
+    ///
+    /// Only fills by matching with drift makers are supported i.e. no external (serum/phoenix) fulfillment
+    ///
+    /// `taker_info` address and account data of the order owner
+    ///
+    /// `taker_order` the order to fill e.g. from a DLOB node
+    ///
+    /// `makers` address and account data of makers to fill against e.g. from resting DLOB nodes
+    ///
+    /// `referrer` the taker's referrer, if any
+    pub fn fill_spot_order(
+        mut self,
+        taker_info: &(Pubkey, User),
+        taker_order: &Order,
+        makers: &[(Pubkey, User)],
+        referrer: Option<ReferrerInfo>,
+    ) -> Self {
+        let (taker, taker_account) = taker_info;
+        let market_index = taker_order.market_index;
+
+        let mut user_accounts = vec![taker_account];
+        user_accounts.extend(makers.iter().map(|(_, maker)| maker));
+
+        let mut accounts = build_accounts(
+            self.program_data,
+            drift::accounts::FillOrder {
+                state: *state_account(),
+                authority: self.authority,
+                filler: self.sub_account,
+                filler_stats: Wallet::derive_stats_account(&self.authority, &constants::PROGRAM_ID),
+                user: *taker,
+                user_stats: Wallet::derive_stats_account(
+                    &taker_account.authority,
+                    &constants::PROGRAM_ID,
+                ),
+            },
+            user_accounts.as_slice(),
+            &[],
+            &[MarketId::spot(market_index), MarketId::QUOTE_SPOT],
+        );
+        accounts.extend(maker_and_referrer_accounts(makers, referrer));
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::FillSpotOrder {
+                order_id: Some(taker_order.order_id),
+                fulfillment_type: Some(SpotFulfillmentType::Match),
+                _maker_order_id: None,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Trigger a trigger order of `user` i.e. once its trigger condition is met
+    ///
+    /// `user_info` address and account data of the order owner
+    ///
+    /// `order` the order to trigger
+    pub fn trigger_order(mut self, user_info: &(Pubkey, User), order: &Order) -> Self {
+        let (user, user_account) = user_info;
+        let perp_writable = [MarketId::perp(order.market_index)];
+        let spot_writable = [MarketId::spot(order.market_index), MarketId::QUOTE_SPOT];
+
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::TriggerOrder {
+                state: *state_account(),
+                authority: self.authority,
+                filler: self.sub_account,
+                user: *user,
+            },
+            &[user_account],
+            &[],
+            if order.market_type == MarketType::Perp {
+                &perp_writable
+            } else {
+                &spot_writable
+            },
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::TriggerOrder {
+                order_id: order.order_id,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Revert the tx if the filler made no fills in it
+    ///
+    /// Add after fill instructions to avoid paying fees for failed fill attempts
+    pub fn revert_fill(mut self) -> Self {
+        let accounts = drift::accounts::RevertFill {
+            state: *state_account(),
+            authority: self.authority,
+            filler: self.sub_account,
+            filler_stats: Wallet::derive_stats_account(&self.authority, &constants::PROGRAM_ID),
+        }
+        .to_account_metas(None);
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::RevertFill {}),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Initialize the user stats account of the authority
+    ///
+    /// Required once per authority, before its first sub-account is initialized
+    pub fn initialize_user_stats(mut self) -> Self {
+        let accounts = drift::accounts::InitializeUserStats {
+            user_stats: Wallet::derive_stats_account(&self.authority, &constants::PROGRAM_ID),
+            state: *state_account(),
+            authority: self.authority,
+            payer: self.authority,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::InitializeUserStats {}),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Initialize the tx sub-account
+    ///
+    /// `name` display name of the sub-account, truncated to 32 bytes
+    ///
+    /// `referrer` referrer of the authority, only applies when initializing sub-account 0
+    pub fn initialize_user(mut self, name: &str, referrer: Option<ReferrerInfo>) -> Self {
+        let mut accounts = drift::accounts::InitializeUser {
+            user: self.sub_account,
+            user_stats: Wallet::derive_stats_account(&self.authority, &constants::PROGRAM_ID),
+            state: *state_account(),
+            authority: self.authority,
+            payer: self.authority,
+            rent: sysvar::rent::ID,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+
+        if let Some(referrer) = referrer {
+            accounts.push(AccountMeta::new(referrer.referrer(), false));
+            accounts.push(AccountMeta::new(referrer.referrer_stats(), false));
+        }
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::InitializeUser {
+                sub_account_id: self.account_data.sub_account_id,
+                name: utils::encode_name(name),
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Set the display name of the tx sub-account
+    pub fn update_user_name(mut self, name: &str) -> Self {
+        let accounts = drift::accounts::UpdateUser {
+            user: self.sub_account,
+            authority: self.authority,
+        }
+        .to_account_metas(None);
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::UpdateUserName {
+                sub_account_id: self.account_data.sub_account_id,
+                name: utils::encode_name(name),
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Set the delegate of the tx sub-account
+    ///
+    /// `delegate` the new delegate, use `Pubkey::default()` to remove the delegate
+    pub fn update_user_delegate(mut self, delegate: Pubkey) -> Self {
+        let accounts = drift::accounts::UpdateUser {
+            user: self.sub_account,
+            authority: self.authority,
+        }
+        .to_account_metas(None);
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::UpdateUserDelegate {
+                sub_account_id: self.account_data.sub_account_id,
+                delegate,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Enable/disable spot margin trading for the tx sub-account
+    pub fn update_user_margin_trading_enabled(mut self, enabled: bool) -> Self {
+        let accounts = build_accounts(
+            self.program_data,
+            drift::accounts::UpdateUser {
+                user: self.sub_account,
+                authority: self.authority,
+            },
+            &[self.account_data.as_ref()],
+            &[],
+            &[],
+        );
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::UpdateUserMarginTradingEnabled {
+                sub_account_id: self.account_data.sub_account_id,
+                margin_trading_enabled: enabled,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Set a custom max. margin ratio for the tx sub-account
+    ///
+    /// `margin_ratio` in `MARGIN_PRECISION` e.g. 5_000 limits the account to 2x leverage, 0 to unset
+    pub fn update_user_custom_margin_ratio(mut self, margin_ratio: u32) -> Self {
+        let accounts = drift::accounts::UpdateUser {
+            user: self.sub_account,
+            authority: self.authority,
+        }
+        .to_account_metas(None);
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::UpdateUserCustomMarginRatio {
+                sub_account_id: self.account_data.sub_account_id,
+                margin_ratio,
+            }),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Delete the tx sub-account, reclaiming its rent
+    ///
+    /// The sub-account must have no open positions or orders
+    pub fn delete_user(mut self) -> Self {
+        let accounts = drift::accounts::DeleteUser {
+            user: self.sub_account,
+            user_stats: Wallet::derive_stats_account(&self.authority, &constants::PROGRAM_ID),
+            state: *state_account(),
+            authority: self.authority,
+        }
+        .to_account_metas(None);
+
+        let ix = Instruction {
+            program_id: constants::PROGRAM_ID,
+            accounts,
+            data: InstructionData::data(&drift::instruction::DeleteUser {}),
+        };
+
+        self.ixs.push(ix);
+        self
+    }
+
+    /// Build the transaction message ready for signing and sending
+    pub fn build(self) -> VersionedMessage {
+        self.compile(self.ixs.as_slice())
+    }
+
+    /// Build the transaction message with compute budget set from simulation and recent fees
+    ///
+    /// The CU limit is set to the simulated consumption plus `config.cu_margin_percent` and the
+    /// priority fee to the `config.fee_percentile` of recent fees for the tx's writable markets.
+    /// Should not be combined with `with_priority_fee`
+    ///
+    /// Returns the tx message or `SdkError::SimulationFailed` if the tx would fail
+    pub async fn build_with_auto_compute_budget<T: AccountProvider>(
+        self,
+        client: &DriftClient<T>,
+        config: AutoComputeBudget,
+    ) -> SdkResult<VersionedMessage> {
+        let mut simulation_ixs = Vec::with_capacity(self.ixs.len() + 2);
+        simulation_ixs.push(ComputeBudgetInstruction::set_compute_unit_limit(
+            constants::MAX_COMPUTE_UNIT_LIMIT,
+        ));
+        simulation_ixs.push(ComputeBudgetInstruction::set_compute_unit_price(0));
+        simulation_ixs.extend_from_slice(self.ixs.as_slice());
+
+        let writable_markets = self.writable_markets();
+        let (simulation, recent_fees) = tokio::try_join!(
+            client.simulate(self.compile(simulation_ixs.as_slice())),
+            client.get_recent_priority_fees(writable_markets.as_slice(), config.fee_window),
+        )?;
+        if let Some(err) = simulation.err {
+            return Err(SdkError::SimulationFailed(err, simulation.error_code));
+        }
+
+        let cu_limit = config.cu_limit(simulation.units_consumed);
+        let microlamports_per_cu = config.priority_fee(recent_fees.as_slice());
+        debug!("auto compute budget: {cu_limit} CUs @ {microlamports_per_cu} µ-lamports/CU");
+
+        Ok(self
+            .with_priority_fee(microlamports_per_cu, Some(cu_limit))
+            .build())
+    }
+
+    /// Return the drift markets written to by the tx
+    fn writable_markets(&self) -> Vec<MarketId> {
+        let mut markets = Vec::<MarketId>::new();
+        let writable_accounts = self
+            .ixs
+            .iter()
+            .flat_map(|ix| ix.accounts.iter())
+            .filter(|a| a.is_writable);
+        for account in writable_accounts {
+            let market = if let Some(market) = self
+                .program_data
+                .perp_market_configs()
+                .iter()
+                .find(|m| m.pubkey == account.pubkey)
+            {
+                MarketId::perp(market.market_index)
+            } else if let Some(market) = self
+                .program_data
+                .spot_market_configs()
+                .iter()
+                .find(|m| m.pubkey == account.pubkey)
+            {
+                MarketId::spot(market.market_index)
+            } else {
+                continue;
+            };
+            if !markets.contains(&market) {
+                markets.push(market);
+            }
+        }
+
+        markets
+    }
+
+    fn compile(&self, ixs: &[Instruction]) -> VersionedMessage {
+        let mut ixs = Cow::Borrowed(ixs);
+        let mut recent_blockhash = Hash::default();
+        if let Some((nonce_account, nonce)) = self.nonce {
+            // advance nonce must be the first ix
+            ixs.to_mut().insert(
+                0,
+                system_instruction::advance_nonce_account(&nonce_account, &self.authority),
+            );
+            recent_blockhash = nonce;
+        }
+
+        if self.legacy {
+            let message =
+                Message::new_with_blockhash(ixs.as_ref(), Some(&self.authority), &recent_blockhash);
+            VersionedMessage::Legacy(message)
+        } else {
+            let message = v0::Message::try_compile(
+                &self.authority,
+                ixs.as_ref(),
+                self.lookup_tables.as_slice(),
+                recent_blockhash,
+            )
+            .expect("ok");
+            VersionedMessage::V0(message)
+        }
+    }
+
+    pub fn program_data(&self) -> &ProgramData {
+        self.program_data
+    }
+
+    pub fn account_data(&self) -> &Cow<'_, User> {
+        &self.account_data
+    }
+}
+
+/// Builds a set of required accounts from a user's open positions and additional given accounts
+///
+/// `base_accounts` base anchor accounts
+///
+/// `user` Drift user account data
+///
+/// `markets_readable` IDs of markets to include as readable
+///
+/// `markets_writable` IDs of markets to include as writable (takes priority over readable)
+///
+/// # Panics
+///  if the user has positions in an unknown market (i.e unsupported by the SDK)
+pub fn build_accounts(
+    program_data: &ProgramData,
+    base_accounts: impl ToAccountMetas,
+    users: &[&User],
+    markets_readable: &[MarketId],
+    markets_writable: &[MarketId],
+) -> Vec<AccountMeta> {
+    // the order of accounts returned must be instruction, oracles, spot, perps see (https://github.com/drift-labs/protocol-v2/blob/master/programs/drift/src/instructions/optional_accounts.rs#L28)
+    let mut seen = [0_u64; 2]; // [spot, perp]
+    let mut accounts = Vec::<RemainingAccount>::default();
+
+    // add accounts to the ordered list
+    let mut include_market = |market_index: u16, market_type: MarketType, writable: bool| {
+        let index_bit = 1_u64 << market_index as u8;
+        // always safe since market type is 0 or 1
+        let seen_by_type = unsafe { seen.get_unchecked_mut(market_type as usize % 2) };
+        if *seen_by_type & index_bit > 0 {
+            return;
+        }
+        *seen_by_type |= index_bit;
+
+        let (account, oracle) = match market_type {
+            MarketType::Spot => {
+                let SpotMarket { pubkey, oracle, .. } = program_data
+                    .spot_market_config_by_index(market_index)
+                    .expect("exists");
+                (
+                    RemainingAccount::Spot {
+                        pubkey: *pubkey,
+                        writable,
+                    },
+                    oracle,
+                )
+            }
+            MarketType::Perp => {
+                let PerpMarket { pubkey, amm, .. } = program_data
+                    .perp_market_config_by_index(market_index)
+                    .expect("exists");
+                (
+                    RemainingAccount::Perp {
+                        pubkey: *pubkey,
+                        writable,
+                    },
+                    &amm.oracle,
+                )
+            }
+        };
+        if let Err(idx) = accounts.binary_search(&account) {
+            accounts.insert(idx, account);
+        }
+        let oracle = RemainingAccount::Oracle { pubkey: *oracle };
+        if let Err(idx) = accounts.binary_search(&oracle) {
+            accounts.insert(idx, oracle);
+        }
+    };
+
+    for MarketId { index, kind } in markets_writable {
+        include_market(*index, *kind, true);
+    }
+
+    for MarketId { index, kind } in markets_readable {
+        include_market(*index, *kind, false);
     }
 
     for user in users {
@@ -1676,6 +2899,67 @@ pub fn build_accounts(
     account_metas
 }
 
+/// Returns the address and ixs to create a temporary wSOL token account owned by `authority`
+///
+/// The account is funded with `lamports` on top of its rent exemption, it should be closed after use.
+/// It is derived with a random seed so that no additional signer is required
+fn temporary_wsol_account(
+    authority: &Pubkey,
+    lamports: u64,
+) -> SdkResult<(Pubkey, [Instruction; 2])> {
+    let seed = &Keypair::new().pubkey().to_string()[..32];
+    let account = Pubkey::create_with_seed(authority, seed, &constants::TOKEN_PROGRAM_ID)
+        .expect("valid seed");
+    let rent = Rent::default().minimum_balance(constants::TOKEN_ACCOUNT_SIZE);
+
+    Ok((
+        account,
+        [
+            system_instruction::create_account_with_seed(
+                authority,
+                &account,
+                authority,
+                seed,
+                rent + lamports,
+                constants::TOKEN_ACCOUNT_SIZE as u64,
+                &constants::TOKEN_PROGRAM_ID,
+            ),
+            initialize_account3(
+                &spl_token::ID,
+                &account,
+                &constants::WRAPPED_SOL_MINT,
+                authority,
+            )?,
+        ],
+    ))
+}
+
+/// Builds the maker and referrer accounts for fill instructions
+///
+/// The program expects (user, user stats) pairs, the referrer is omitted if it is already one of the makers
+fn maker_and_referrer_accounts(
+    makers: &[(Pubkey, User)],
+    referrer: Option<ReferrerInfo>,
+) -> Vec<AccountMeta> {
+    let mut accounts = Vec::with_capacity(makers.len() * 2 + 2);
+    for (maker, maker_account) in makers {
+        accounts.push(AccountMeta::new(*maker, false));
+        accounts.push(AccountMeta::new(
+            Wallet::derive_stats_account(&maker_account.authority, &constants::PROGRAM_ID),
+            false,
+        ));
+    }
+
+    if let Some(referrer) = referrer {
+        if !makers.iter().any(|(m, _)| *m == referrer.referrer()) {
+            accounts.push(AccountMeta::new(referrer.referrer(), false));
+            accounts.push(AccountMeta::new(referrer.referrer_stats(), false));
+        }
+    }
+
+    accounts
+}
+
 /// Fetch all market accounts from drift program (does not require `getProgramAccounts` RPC which is often unavailable)
 pub async fn get_market_accounts(
     client: &RpcClient,
@@ -1717,10 +3001,10 @@ pub async fn get_market_accounts(
 }
 
 /// Drift wallet
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Wallet {
-    /// The signing keypair, it could be authority or delegate
-    signer: Arc<Keypair>,
+    /// The tx signer, it could be authority or delegate
+    signer: Arc<dyn signer::DriftSigner>,
     /// The drift 'authority' account
     /// user (sub)accounts are derived from this
     authority: Pubkey,
@@ -1731,7 +3015,7 @@ pub struct Wallet {
 impl Wallet {
     /// Returns true if the wallet is configured for delegated signing
     pub fn is_delegated(&self) -> bool {
-        self.authority != self.signer.pubkey() && self.signer.pubkey().is_on_curve()
+        self.authority != self.signer.address() && self.signer.address().is_on_curve()
     }
     /// Init wallet from a string that could be either a file path or the encoded key, uses default sub-account
     pub fn try_from_str(path_or_key: &str) -> SdkResult<Self> {
@@ -1741,7 +3025,7 @@ impl Wallet {
     /// Construct a read-only wallet
     pub fn read_only(authority: Pubkey) -> Self {
         Self {
-            signer: Arc::new(Keypair::from_bytes(&[0_u8; 64]).expect("empty signer")),
+            signer: Arc::new(signer::ReadOnlySigner),
             authority,
             stats: Wallet::derive_stats_account(&authority, &constants::PROGRAM_ID),
         }
@@ -1769,6 +3053,17 @@ impl Wallet {
             signer: Arc::new(authority),
         }
     }
+    /// Init wallet with a custom signer e.g. `RemoteSigner`, uses default sub-account
+    ///
+    /// `signer` tx signer, its address is used as the authority
+    pub fn from_signer(signer: impl signer::DriftSigner) -> Self {
+        let authority = signer.address();
+        Self {
+            stats: Wallet::derive_stats_account(&authority, &constants::PROGRAM_ID),
+            authority,
+            signer: Arc::new(signer),
+        }
+    }
     /// Convert the wallet into a delegated one by providing the `authority` public key
     pub fn to_delegated(&mut self, authority: Pubkey) {
         self.stats = Wallet::derive_stats_account(&authority, &constants::PROGRAM_ID);
@@ -1791,6 +3086,12 @@ impl Wallet {
         account_drift_pda
     }
 
+    /// Calculate the address of a durable nonce account created with `seed`
+    pub fn derive_nonce_account(authority: &Pubkey, seed: &str) -> SdkResult<Pubkey> {
+        Pubkey::create_with_seed(authority, seed, &system_program::ID)
+            .map_err(|_| SdkError::InvalidSeed)
+    }
+
     /// Calculate the address of a drift stats account
     pub fn derive_stats_account(account: &Pubkey, program: &Pubkey) -> Pubkey {
         let (account_drift_pda, _seed) =
@@ -1799,14 +3100,25 @@ impl Wallet {
     }
 
     /// Signs the given tx `message` returning the tx on success
-    pub fn sign_tx(
+    pub async fn sign_tx(
         &self,
         mut message: VersionedMessage,
         recent_block_hash: Hash,
     ) -> SdkResult<VersionedTransaction> {
         message.set_recent_blockhash(recent_block_hash);
-        let signer: &dyn Signer = self.signer.as_ref();
-        VersionedTransaction::try_new(message, &[signer]).map_err(Into::into)
+        let required_signers = message.header().num_required_signatures as usize;
+        if required_signers > 1 {
+            return Err(SignerError::NotEnoughSigners.into());
+        }
+        if message.static_account_keys().first() != Some(&self.signer.address()) {
+            return Err(SignerError::KeypairPubkeyMismatch.into());
+        }
+        let signature = self.signer.sign_message_bytes(&message.serialize()).await?;
+
+        Ok(VersionedTransaction {
+            signatures: vec![signature],
+            message,
+        })
     }
 
     /// Return the wallet authority address
@@ -1815,7 +3127,7 @@ impl Wallet {
     }
     /// Return the wallet signing address
     pub fn signer(&self) -> Pubkey {
-        self.signer.pubkey()
+        self.signer.address()
     }
     /// Return the drift user stats address
     pub fn stats(&self) -> &Pubkey {
@@ -1831,6 +3143,16 @@ impl Wallet {
     }
 }
 
+impl std::fmt::Debug for Wallet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Wallet")
+            .field("signer", &self.signer.address())
+            .field("authority", &self.authority)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
 impl From<Keypair> for Wallet {
     fn from(value: Keypair) -> Self {
         Self::new(value)
@@ -1839,16 +3161,21 @@ impl From<Keypair> for Wallet {
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
+    use std::{
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
 
-    use drift::state::perp_market::PerpMarket;
+    use drift::state::perp_market::{PerpMarket, AMM};
     use serde_json::json;
     use solana_account_decoder::{UiAccount, UiAccountData};
     use solana_client::{
         rpc_client::Mocks,
         rpc_request::RpcRequest,
-        rpc_response::{Response, RpcResponseContext},
+        rpc_response::{Response, RpcBlockhash, RpcResponseContext},
+        rpc_sender::{RpcSender, RpcTransportStats},
     };
+    use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
 
     use super::*;
 
@@ -1861,6 +3188,20 @@ mod tests {
         rpc_mocks: Mocks,
         account_provider_mocks: Mocks,
         keypair: Keypair,
+    ) -> DriftClient<RpcAccountProvider> {
+        setup_with_rpc(
+            RpcClient::new_mock_with_mocks(DEVNET_ENDPOINT.to_string(), rpc_mocks),
+            account_provider_mocks,
+            keypair,
+        )
+        .await
+    }
+
+    /// Init a new `DriftClient` sending txs via `rpc_client`
+    async fn setup_with_rpc(
+        rpc_client: RpcClient,
+        account_provider_mocks: Mocks,
+        keypair: Keypair,
     ) -> DriftClient<RpcAccountProvider> {
         let perp_market_map = MarketMap::<PerpMarket>::new(
             CommitmentConfig::processed(),
@@ -1874,7 +3215,7 @@ mod tests {
         );
 
         let backend = DriftClientBackend {
-            rpc_client: RpcClient::new_mock_with_mocks(DEVNET_ENDPOINT.to_string(), rpc_mocks),
+            rpc_client,
             account_provider: RpcAccountProvider {
                 client: Arc::new(RpcClient::new_mock_with_mocks(
                     DEVNET_ENDPOINT.to_string(),
@@ -1907,6 +3248,50 @@ mod tests {
         }
     }
 
+    /// Init `ProgramData` with some spot and perp markets for building txs
+    fn test_program_data() -> ProgramData {
+        let spot_markets = (0..3)
+            .map(|market_index| SpotMarket {
+                market_index,
+                pubkey: derive_spot_market_account(market_index),
+                oracle: Pubkey::new_unique(),
+                mint: if market_index == 1 {
+                    constants::WRAPPED_SOL_MINT
+                } else {
+                    Pubkey::new_unique()
+                },
+                ..SpotMarket::default()
+            })
+            .collect();
+        let perp_markets = (0..3)
+            .map(|market_index| PerpMarket {
+                market_index,
+                pubkey: derive_perp_market_account(market_index),
+                amm: AMM {
+                    oracle: Pubkey::new_unique(),
+                    ..AMM::default()
+                },
+                ..PerpMarket::default()
+            })
+            .collect();
+
+        ProgramData::new(
+            spot_markets,
+            perp_markets,
+            AddressLookupTableAccount {
+                key: Pubkey::new_unique(),
+                addresses: vec![],
+            },
+        )
+    }
+
+    /// true if `account` is included in the ix accounts with given mutability
+    fn has_account(ix: &Instruction, account: Pubkey, writable: bool) -> bool {
+        ix.accounts
+            .iter()
+            .any(|a| a.pubkey == account && a.is_writable == writable)
+    }
+
     #[tokio::test]
     async fn test_backend_send_sync() {
         let account_mocks = Mocks::default();
@@ -2037,4 +3422,520 @@ mod tests {
         assert_eq!(rw.stats, ro.stats);
         assert_eq!(rw.default_sub_account(), ro.default_sub_account());
     }
+
+    #[test]
+    fn settle_pnl_accounts() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let account_data = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+        let settlee = (
+            Pubkey::new_unique(),
+            User {
+                authority: Pubkey::new_unique(),
+                ..Default::default()
+            },
+        );
+        let quote_market = program_data.spot_market_config_by_index(0).unwrap();
+        let perp_market = program_data.perp_market_config_by_index(1).unwrap();
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.default_sub_account(),
+            Cow::Borrowed(&account_data),
+            false,
+        )
+        .settle_pnl(1, None)
+        .settle_pnl(1, Some(&settlee));
+
+        let own_ix = &builder.ixs[0];
+        assert!(has_account(own_ix, wallet.default_sub_account(), true));
+        assert!(has_account(own_ix, quote_market.pubkey, true));
+        assert!(has_account(own_ix, perp_market.pubkey, true));
+        assert!(has_account(own_ix, perp_market.amm.oracle, false));
+
+        let settlee_ix = &builder.ixs[1];
+        assert!(has_account(settlee_ix, settlee.0, true));
+        assert!(!settlee_ix
+            .accounts
+            .iter()
+            .any(|a| a.pubkey == wallet.default_sub_account()));
+    }
+
+    #[test]
+    fn settle_multiple_pnls_accounts() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let account_data = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.default_sub_account(),
+            Cow::Borrowed(&account_data),
+            false,
+        )
+        .settle_multiple_pnls(&[0, 2], SettlePnlMode::TrySettle, None);
+
+        let ix = &builder.ixs[0];
+        for market_index in [0, 2] {
+            let market = program_data
+                .perp_market_config_by_index(market_index)
+                .unwrap();
+            assert!(has_account(ix, market.pubkey, true));
+        }
+        let unsettled_market = program_data.perp_market_config_by_index(1).unwrap();
+        assert!(!ix
+            .accounts
+            .iter()
+            .any(|a| a.pubkey == unsettled_market.pubkey));
+    }
+
+    #[test]
+    fn liquidate_perp_accounts() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let account_data = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+        let mut liquidatee_account = User {
+            authority: Pubkey::new_unique(),
+            ..Default::default()
+        };
+        liquidatee_account.perp_positions[0].market_index = 2;
+        liquidatee_account.perp_positions[0].base_asset_amount = 1;
+        let liquidatee = (Pubkey::new_unique(), liquidatee_account);
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.default_sub_account(),
+            Cow::Borrowed(&account_data),
+            false,
+        )
+        .liquidate_perp(1, &liquidatee, 1_000, None);
+
+        let ix = &builder.ixs[0];
+        let market = program_data.perp_market_config_by_index(1).unwrap();
+        let position_market = program_data.perp_market_config_by_index(2).unwrap();
+        assert!(has_account(ix, liquidatee.0, true));
+        assert!(has_account(
+            ix,
+            Wallet::derive_stats_account(&liquidatee.1.authority, &constants::PROGRAM_ID),
+            true
+        ));
+        assert!(has_account(ix, wallet.default_sub_account(), true));
+        assert!(has_account(ix, market.pubkey, true));
+        assert!(has_account(ix, position_market.pubkey, false));
+    }
+
+    #[test]
+    fn fill_perp_order_accounts() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let account_data = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+        let mut taker_account = User {
+            authority: Pubkey::new_unique(),
+            ..Default::default()
+        };
+        let taker_order = Order {
+            order_id: 7,
+            market_index: 1,
+            market_type: MarketType::Perp,
+            ..Default::default()
+        };
+        taker_account.orders[0] = taker_order;
+        let taker = (Pubkey::new_unique(), taker_account);
+        let makers: Vec<(Pubkey, User)> = (0..2)
+            .map(|_| {
+                (
+                    Pubkey::new_unique(),
+                    User {
+                        authority: Pubkey::new_unique(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        // referrer is the first maker, should not be included twice
+        let referrer = ReferrerInfo::new(
+            makers[0].0,
+            Wallet::derive_stats_account(&makers[0].1.authority, &constants::PROGRAM_ID),
+        );
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.default_sub_account(),
+            Cow::Borrowed(&account_data),
+            false,
+        )
+        .fill_perp_order(&taker, &taker_order, makers.as_slice(), Some(referrer))
+        .revert_fill();
+
+        let ix = &builder.ixs[0];
+        let market = program_data.perp_market_config_by_index(1).unwrap();
+        assert!(has_account(ix, taker.0, true));
+        assert!(has_account(ix, market.pubkey, true));
+
+        let tail: Vec<Pubkey> = ix.accounts[ix.accounts.len() - 4..]
+            .iter()
+            .map(|a| a.pubkey)
+            .collect();
+        assert_eq!(
+            tail,
+            vec![
+                makers[0].0,
+                Wallet::derive_stats_account(&makers[0].1.authority, &constants::PROGRAM_ID),
+                makers[1].0,
+                Wallet::derive_stats_account(&makers[1].1.authority, &constants::PROGRAM_ID),
+            ]
+        );
+        assert_eq!(builder.ixs[1].accounts.len(), 4);
+    }
+
+    #[test]
+    fn initialize_user_accounts() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let referrer = Pubkey::new_unique();
+        let referrer_info = ReferrerInfo::new(
+            Wallet::derive_user_account(&referrer, 0, &constants::PROGRAM_ID),
+            Wallet::derive_stats_account(&referrer, &constants::PROGRAM_ID),
+        );
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.sub_account(2),
+            Cow::Owned(User {
+                authority: *wallet.authority(),
+                sub_account_id: 2,
+                ..Default::default()
+            }),
+            false,
+        )
+        .initialize_user_stats()
+        .initialize_user("strategy", Some(referrer_info))
+        .update_user_delegate(Pubkey::new_unique());
+
+        let stats_ix = &builder.ixs[0];
+        assert!(has_account(stats_ix, *wallet.stats(), true));
+
+        let init_ix = &builder.ixs[1];
+        assert!(has_account(init_ix, wallet.sub_account(2), true));
+        let tail: Vec<Pubkey> = init_ix.accounts[init_ix.accounts.len() - 2..]
+            .iter()
+            .map(|a| a.pubkey)
+            .collect();
+        assert_eq!(
+            tail,
+            vec![referrer_info.referrer(), referrer_info.referrer_stats()]
+        );
+
+        let delegate_ix = &builder.ixs[2];
+        assert_eq!(delegate_ix.accounts.len(), 2);
+        assert!(has_account(delegate_ix, wallet.sub_account(2), true));
+    }
+
+    #[test]
+    fn deposit_and_withdraw_sol() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let account_data = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+        let sol_market = program_data.spot_market_config_by_index(1).unwrap();
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.default_sub_account(),
+            Cow::Borrowed(&account_data),
+            false,
+        )
+        .deposit_sol(1_000_000, None)
+        .unwrap()
+        .withdraw_sol(500_000, None)
+        .unwrap();
+
+        assert_eq!(builder.ixs.len(), 8);
+        for ixs in builder.ixs.chunks(4) {
+            let [create, init, drift_ix, close] = ixs else {
+                unreachable!()
+            };
+            let wsol_account = init.accounts[0].pubkey;
+            assert_eq!(create.program_id, system_program::ID);
+            assert!(has_account(create, wsol_account, true));
+            assert_eq!(init.program_id, constants::TOKEN_PROGRAM_ID);
+            assert!(has_account(drift_ix, wsol_account, true));
+            assert!(has_account(
+                drift_ix,
+                constants::derive_spot_market_vault(sol_market.market_index),
+                true
+            ));
+            assert_eq!(close.accounts[0].pubkey, wsol_account);
+            assert_eq!(close.accounts[1].pubkey, *wallet.authority());
+        }
+    }
+
+    #[test]
+    fn transfer_deposit_accounts() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let mut from_account = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+        from_account.spot_positions[0].market_index = 2;
+        from_account.spot_positions[0].scaled_balance = 1;
+        let mut to_account = User {
+            authority: *wallet.authority(),
+            sub_account_id: 1,
+            ..Default::default()
+        };
+        to_account.perp_positions[0].market_index = 1;
+        to_account.perp_positions[0].base_asset_amount = 1;
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.sub_account(0),
+            Cow::Borrowed(&from_account),
+            false,
+        )
+        .transfer_deposit(&(wallet.sub_account(1), to_account), 0, 1_000);
+
+        let ix = &builder.ixs[0];
+        assert!(has_account(ix, wallet.sub_account(0), true));
+        assert!(has_account(ix, wallet.sub_account(1), true));
+        assert!(has_account(
+            ix,
+            program_data.spot_market_config_by_index(0).unwrap().pubkey,
+            true
+        ));
+        // positions of both users are included
+        assert!(has_account(
+            ix,
+            program_data.spot_market_config_by_index(2).unwrap().pubkey,
+            false
+        ));
+        assert!(has_account(
+            ix,
+            program_data.perp_market_config_by_index(1).unwrap().pubkey,
+            false
+        ));
+    }
+
+    #[test]
+    fn tx_writable_markets() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let account_data = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+
+        let builder = TransactionBuilder::new(
+            &program_data,
+            wallet.default_sub_account(),
+            Cow::Borrowed(&account_data),
+            false,
+        )
+        .settle_pnl(1, None)
+        .settle_pnl(2, None);
+
+        assert_eq!(
+            builder.writable_markets(),
+            vec![MarketId::QUOTE_SPOT, MarketId::perp(1), MarketId::perp(2)],
+        );
+    }
+
+    #[tokio::test]
+    async fn sign_and_confirm_landed() {
+        let client = setup(Default::default(), Default::default(), Keypair::new()).await;
+        let tx = client
+            .init_new_account_tx(0)
+            .initialize_user_stats()
+            .legacy()
+            .build();
+        let config = SendConfig {
+            rebroadcast_interval: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let (_, outcome) = client.sign_and_confirm(tx, config).await.unwrap();
+        assert_eq!(outcome, TxOutcome::Landed { slot: 1, err: None });
+    }
+
+    #[tokio::test]
+    async fn sign_and_confirm_expired() {
+        let mut rpc_mocks = Mocks::default();
+        rpc_mocks.insert(
+            RpcRequest::GetSignatureStatuses,
+            json!(Response {
+                context: RpcResponseContext::new(12_345),
+                value: vec![Option::<()>::None],
+            }),
+        );
+        rpc_mocks.insert(RpcRequest::GetBlockHeight, json!(1_000_u64));
+        let client = setup(rpc_mocks, Default::default(), Keypair::new()).await;
+        client
+            .backend
+            .blockhash_subscriber
+            .write()
+            .await
+            .push_blockhash((Hash::new_unique(), 999));
+        let tx = client
+            .init_new_account_tx(0)
+            .initialize_user_stats()
+            .legacy()
+            .build();
+        let config = SendConfig {
+            rebroadcast_interval: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let (_, outcome) = client.sign_and_confirm(tx, config).await.unwrap();
+        assert_eq!(outcome, TxOutcome::Expired);
+    }
+
+    /// RPC sender replying to every status query with the same tx status
+    struct TxStatusSender {
+        status: Option<TransactionConfirmationStatus>,
+        block_height: u64,
+        /// number of txs sent
+        sends: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl RpcSender for TxStatusSender {
+        async fn send(
+            &self,
+            request: RpcRequest,
+            params: serde_json::Value,
+        ) -> solana_client::client_error::Result<serde_json::Value> {
+            let value = match request {
+                RpcRequest::GetVersion => json!({ "solana-core": "1.17.0" }),
+                RpcRequest::GetBlockHeight => json!(self.block_height),
+                RpcRequest::GetLatestBlockhash => json!(Response {
+                    context: RpcResponseContext::new(1),
+                    value: RpcBlockhash {
+                        blockhash: Hash::new_unique().to_string(),
+                        last_valid_block_height: self.block_height + 150,
+                    },
+                }),
+                RpcRequest::GetSignatureStatuses => json!(Response {
+                    context: RpcResponseContext::new(1),
+                    value: vec![self.status.clone().map(|confirmation_status| {
+                        TransactionStatus {
+                            slot: 1,
+                            confirmations: Some(0),
+                            status: Ok(()),
+                            err: None,
+                            confirmation_status: Some(confirmation_status),
+                        }
+                    })],
+                }),
+                RpcRequest::SendTransaction => {
+                    self.sends.fetch_add(1, Ordering::Relaxed);
+                    // the tx signature directly follows the 1 byte signature count
+                    let tx = base64::decode(params[0].as_str().unwrap()).unwrap();
+                    json!(Signature::try_from(&tx[1..65]).unwrap().to_string())
+                }
+                _ => unimplemented!("{request}"),
+            };
+            Ok(value)
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            Default::default()
+        }
+
+        fn url(&self) -> String {
+            "TxStatusSender".to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn sign_and_confirm_processed_is_not_resigned() {
+        let sends = Arc::new(AtomicUsize::default());
+        let rpc_client = RpcClient::new_sender(
+            TxStatusSender {
+                status: Some(TransactionConfirmationStatus::Processed),
+                block_height: 1_000,
+                sends: Arc::clone(&sends),
+            },
+            Default::default(),
+        );
+        let client = setup_with_rpc(rpc_client, Default::default(), Keypair::new()).await;
+        client
+            .backend
+            .blockhash_subscriber
+            .write()
+            .await
+            .push_blockhash((Hash::new_unique(), 999));
+        let tx = client
+            .init_new_account_tx(0)
+            .initialize_user_stats()
+            .legacy()
+            .build();
+        let config = SendConfig {
+            commitment: CommitmentConfig::finalized(),
+            rebroadcast_interval: Duration::from_millis(1),
+            timeout: Duration::from_millis(50),
+            max_resigns: 1,
+        };
+
+        let (_, outcome) = client.sign_and_confirm(tx, config).await.unwrap();
+        assert_eq!(outcome, TxOutcome::Dropped);
+        // only the initial send, never re-signed or rebroadcast
+        assert_eq!(sends.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn durable_nonce_tx() {
+        let program_data = test_program_data();
+        let wallet = Wallet::new(Keypair::new());
+        let account_data = User {
+            authority: *wallet.authority(),
+            ..Default::default()
+        };
+        let nonce_account = Wallet::derive_nonce_account(wallet.authority(), "nonce").unwrap();
+        let nonce = Hash::new_unique();
+
+        for legacy in [true, false] {
+            let mut builder = TransactionBuilder::new(
+                &program_data,
+                wallet.default_sub_account(),
+                Cow::Borrowed(&account_data),
+                false,
+            )
+            .with_durable_nonce(nonce_account, nonce)
+            .settle_pnl(1, None)
+            .with_priority_fee(1, Some(200_000));
+            if legacy {
+                builder = builder.legacy();
+            }
+            let tx = builder.build();
+
+            assert!(utils::uses_durable_nonce(&tx));
+            assert_eq!(*tx.recent_blockhash(), nonce);
+            assert_eq!(tx.instructions().len(), 4);
+        }
+
+        let tx = TransactionBuilder::new(
+            &program_data,
+            wallet.default_sub_account(),
+            Cow::Borrowed(&account_data),
+            false,
+        )
+        .settle_pnl(1, None)
+        .build();
+        assert!(!utils::uses_durable_nonce(&tx));
+    }
 }
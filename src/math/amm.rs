@@ -0,0 +1,133 @@
+use drift::{controller::position::PositionDirection, state::perp_market::AMM};
+
+/// Calculate the liquidity levels the vAMM offers on one side of the book
+///
+/// Levels are swapped successively from the spread-adjusted bid/ask reserves. The total size is
+/// bounded by the open base capacity of the AMM and the max. fill reserve fraction, split evenly
+/// into `num_levels` levels of order step size granularity.
+///
+/// `direction` `Long` for the vAMM bids, `Short` for the vAMM asks
+///
+/// Returns (price, base amount) levels ordered from best to worst price
+pub fn calculate_vamm_levels(
+    amm: &AMM,
+    direction: PositionDirection,
+    num_levels: usize,
+) -> Vec<(u64, u64)> {
+    let (mut base_reserve, mut quote_reserve, open_base) = match direction {
+        // vAMM bids buy base, growing the base reserve
+        PositionDirection::Long => (
+            amm.bid_base_asset_reserve,
+            amm.bid_quote_asset_reserve,
+            amm.max_base_asset_reserve
+                .saturating_sub(amm.base_asset_reserve),
+        ),
+        PositionDirection::Short => (
+            amm.ask_base_asset_reserve,
+            amm.ask_quote_asset_reserve,
+            amm.base_asset_reserve
+                .saturating_sub(amm.min_base_asset_reserve),
+        ),
+    };
+    if base_reserve == 0 || quote_reserve == 0 || num_levels == 0 {
+        return vec![];
+    }
+
+    let mut open_base = open_base;
+    if amm.max_fill_reserve_fraction > 0 {
+        open_base = open_base.min(amm.base_asset_reserve / amm.max_fill_reserve_fraction as u128);
+    }
+    let step_size = (amm.order_step_size as u128).max(1);
+    let level_size = open_base / num_levels as u128 / step_size * step_size;
+    let Some(k) = base_reserve.checked_mul(quote_reserve) else {
+        return vec![];
+    };
+    if level_size == 0 {
+        return vec![];
+    }
+
+    let tick_size = amm.order_tick_size.max(1);
+    let mut levels = Vec::with_capacity(num_levels);
+    for _ in 0..num_levels {
+        let (new_base_reserve, new_quote_reserve, quote_amount) = match direction {
+            PositionDirection::Long => {
+                let new_base_reserve = base_reserve + level_size;
+                let new_quote_reserve = k / new_base_reserve;
+                (
+                    new_base_reserve,
+                    new_quote_reserve,
+                    quote_reserve - new_quote_reserve,
+                )
+            }
+            PositionDirection::Short => {
+                if level_size >= base_reserve {
+                    break;
+                }
+                let new_base_reserve = base_reserve - level_size;
+                let new_quote_reserve = k.div_ceil(new_base_reserve);
+                (
+                    new_base_reserve,
+                    new_quote_reserve,
+                    new_quote_reserve - quote_reserve,
+                )
+            }
+        };
+        // reserve and base precision match so only the peg remains to convert to price precision
+        let price = (quote_amount * amm.peg_multiplier / level_size) as u64;
+        let price = match direction {
+            PositionDirection::Long => price / tick_size * tick_size,
+            PositionDirection::Short => price.div_ceil(tick_size) * tick_size,
+        };
+        levels.push((price, level_size as u64));
+
+        base_reserve = new_base_reserve;
+        quote_reserve = new_quote_reserve;
+    }
+
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use drift::math::constants::{
+        AMM_RESERVE_PRECISION, BASE_PRECISION_U64, PEG_PRECISION, PRICE_PRECISION_U64,
+    };
+
+    use super::*;
+
+    #[test]
+    fn vamm_levels() {
+        let reserve = 1_000 * AMM_RESERVE_PRECISION;
+        let amm = AMM {
+            base_asset_reserve: reserve,
+            quote_asset_reserve: reserve,
+            bid_base_asset_reserve: reserve,
+            bid_quote_asset_reserve: reserve,
+            ask_base_asset_reserve: reserve,
+            ask_quote_asset_reserve: reserve,
+            max_base_asset_reserve: 2 * reserve,
+            min_base_asset_reserve: reserve / 2,
+            max_fill_reserve_fraction: 100,
+            peg_multiplier: 20 * PEG_PRECISION,
+            order_step_size: BASE_PRECISION_U64 / 1_000,
+            order_tick_size: 100,
+            ..Default::default()
+        };
+
+        let bids = calculate_vamm_levels(&amm, PositionDirection::Long, 5);
+        let asks = calculate_vamm_levels(&amm, PositionDirection::Short, 5);
+        assert_eq!(bids.len(), 5);
+        assert_eq!(asks.len(), 5);
+
+        // 10 base capacity split evenly
+        assert!(bids.iter().all(|(_, size)| *size == 2 * BASE_PRECISION_U64));
+        assert!(bids.windows(2).all(|w| w[0].0 > w[1].0));
+        assert!(asks.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(bids[0].0 < 20 * PRICE_PRECISION_U64);
+        assert!(asks[0].0 > 20 * PRICE_PRECISION_U64);
+        assert!(bids[0].0 > 19 * PRICE_PRECISION_U64);
+        assert!(asks[0].0 < 21 * PRICE_PRECISION_U64);
+
+        assert!(calculate_vamm_levels(&AMM::default(), PositionDirection::Long, 5).is_empty());
+    }
+}
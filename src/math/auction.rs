@@ -17,7 +17,10 @@ pub fn is_auction_complete(order: &Order, slot: u64) -> bool {
 
 #[track_caller]
 pub fn get_auction_price(order: &Order, slot: u64, price: i64) -> i128 {
-    if is_one_of_variant(
+    if order.order_type == OrderType::Limit && order.oracle_price_offset != 0 {
+        // floating limit orders auction relative to the oracle
+        get_auction_price_for_oracle_offset_auction(order, slot, price)
+    } else if is_one_of_variant(
         &order.order_type,
         &[
             OrderType::Market,
@@ -35,29 +38,7 @@ pub fn get_auction_price(order: &Order, slot: u64, price: i64) -> i128 {
 }
 
 fn get_auction_price_for_fixed_auction(order: &Order, slot: u64) -> i128 {
-    let slots_elapsed = slot - order.slot;
-
-    let auction_start_price = order.auction_start_price as i128;
-    let auction_end_price = order.auction_end_price as i128;
-    let delta_denominator: i128 = order.auction_duration.into();
-    let delta_numerator: i128 = min(slots_elapsed, order.auction_duration as u64).into();
-
-    if delta_denominator == 0 {
-        return auction_start_price;
-    }
-
-    match order.direction {
-        PositionDirection::Long => {
-            let price_delta =
-                auction_end_price - auction_start_price * delta_numerator / delta_denominator;
-            auction_start_price + price_delta
-        }
-        PositionDirection::Short => {
-            let price_delta =
-                auction_start_price - auction_end_price * delta_numerator / delta_denominator;
-            auction_start_price - price_delta
-        }
-    }
+    interpolate_auction_price(order, slot)
 }
 
 fn get_auction_price_for_oracle_offset_auction(
@@ -65,7 +46,12 @@ fn get_auction_price_for_oracle_offset_auction(
     slot: u64,
     oracle_price: i64,
 ) -> i128 {
-    let slots_elapsed = slot - order.slot;
+    oracle_price as i128 + interpolate_auction_price(order, slot)
+}
+
+/// Linearly move from the auction start to end price over the auction duration
+fn interpolate_auction_price(order: &Order, slot: u64) -> i128 {
+    let slots_elapsed = slot.saturating_sub(order.slot);
 
     let auction_start_price = order.auction_start_price as i128;
     let auction_end_price = order.auction_end_price as i128;
@@ -76,18 +62,62 @@ fn get_auction_price_for_oracle_offset_auction(
         return auction_start_price;
     }
 
-    let price_offset = match order.direction {
+    match order.direction {
         PositionDirection::Long => {
             let price_delta =
-                auction_end_price - auction_start_price * delta_numerator / delta_denominator;
+                (auction_end_price - auction_start_price) * delta_numerator / delta_denominator;
             auction_start_price + price_delta
         }
         PositionDirection::Short => {
             let price_delta =
-                auction_start_price - auction_end_price * delta_numerator / delta_denominator;
+                (auction_start_price - auction_end_price) * delta_numerator / delta_denominator;
             auction_start_price - price_delta
         }
-    };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auction_price_progression() {
+        let order = Order {
+            order_type: OrderType::Market,
+            direction: PositionDirection::Long,
+            slot: 10,
+            auction_duration: 10,
+            auction_start_price: 100,
+            auction_end_price: 200,
+            ..Order::default()
+        };
+        assert_eq!(get_auction_price(&order, 10, 0), 100);
+        assert_eq!(get_auction_price(&order, 15, 0), 150);
+        assert_eq!(get_auction_price(&order, 20, 0), 200);
+        assert_eq!(get_auction_price(&order, 30, 0), 200);
+        // slot behind the order
+        assert_eq!(get_auction_price(&order, 5, 0), 100);
 
-    oracle_price as i128 + price_offset
+        let order = Order {
+            direction: PositionDirection::Short,
+            auction_start_price: 200,
+            auction_end_price: 100,
+            ..order
+        };
+        assert_eq!(get_auction_price(&order, 15, 0), 150);
+        assert_eq!(get_auction_price(&order, 20, 0), 100);
+
+        let order = Order {
+            order_type: OrderType::Oracle,
+            auction_start_price: -10,
+            auction_end_price: 10,
+            direction: PositionDirection::Long,
+            ..order
+        };
+        assert_eq!(get_auction_price(&order, 15, 1_000), 1_000);
+        assert_eq!(get_auction_price(&order, 20, 1_000), 1_010);
+
+        assert!(!is_auction_complete(&order, 20));
+        assert!(is_auction_complete(&order, 21));
+    }
 }
@@ -1,4 +1,5 @@
 pub mod account_map_builder;
+pub mod amm;
 pub mod auction;
 pub mod leverage;
 pub mod liquidation;
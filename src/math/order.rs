@@ -35,7 +35,7 @@ pub fn get_limit_price(
     }
 }
 
-fn has_auction_price(order: &Order, slot: u64) -> bool {
+pub(crate) fn has_auction_price(order: &Order, slot: u64) -> bool {
     !is_auction_complete(order, slot)
         && (order.auction_start_price != 0 || order.auction_end_price != 0)
 }
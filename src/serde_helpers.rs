@@ -0,0 +1,292 @@
+//! serde for drift program types that don't implement it
+//!
+//! Enums are encoded by variant name and orders by field, so recorded events and snapshots
+//! stay readable and independent of the program's binary layout
+use drift::{
+    controller::position::PositionDirection,
+    state::{
+        events::{
+            DepositDirection, DepositExplanation, LPAction, LiquidationType, SettlePnlExplanation,
+        },
+        insurance_fund_stake::StakeAction,
+        user::{MarketType, Order, OrderStatus, OrderTriggerCondition, OrderType},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+/// Names of the variants of a fieldless drift program enum
+pub(crate) trait VariantNames: Copy + PartialEq + Sized + 'static {
+    /// every variant with its name
+    const VARIANTS: &'static [(Self, &'static str)];
+
+    fn name(&self) -> Option<&'static str> {
+        Self::VARIANTS
+            .iter()
+            .find(|(variant, _)| variant == self)
+            .map(|(_, name)| *name)
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        Self::VARIANTS
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(variant, _)| *variant)
+    }
+}
+
+macro_rules! variant_names {
+    ($($ty:ty => [$($variant:ident),+ $(,)?]),+ $(,)?) => {
+        $(
+            impl VariantNames for $ty {
+                const VARIANTS: &'static [(Self, &'static str)] =
+                    &[$((<$ty>::$variant, stringify!($variant))),+];
+            }
+        )+
+    };
+}
+
+variant_names! {
+    PositionDirection => [Long, Short],
+    MarketType => [Spot, Perp],
+    OrderStatus => [Init, Open, Filled, Canceled],
+    OrderType => [Market, Limit, TriggerMarket, TriggerLimit, Oracle],
+    OrderTriggerCondition => [Above, Below, TriggeredAbove, TriggeredBelow],
+    DepositDirection => [Deposit, Withdraw],
+    DepositExplanation => [None, Transfer, Borrow, RepayBorrow],
+    LiquidationType => [
+        LiquidatePerp,
+        LiquidateSpot,
+        LiquidateBorrowForPerpPnl,
+        LiquidatePerpPnlForDeposit,
+        PerpBankruptcy,
+        SpotBankruptcy,
+    ],
+    SettlePnlExplanation => [None, ExpiredPosition],
+    LPAction => [AddLiquidity, RemoveLiquidity, SettleLiquidity, RemoveLiquidityDerisk],
+    StakeAction => [
+        Stake,
+        UnstakeRequest,
+        UnstakeCancelRequest,
+        Unstake,
+        UnstakeTransfer,
+        StakeTransfer,
+    ],
+}
+
+/// serde for fieldless drift program enums by variant name e.g. `"Long"`
+pub(crate) mod serde_variant {
+    use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+    use super::VariantNames;
+
+    pub fn serialize<T: VariantNames, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let name = value
+            .name()
+            .ok_or_else(|| <S::Error as ser::Error>::custom("unnamed variant"))?;
+        serializer.serialize_str(name)
+    }
+    pub fn deserialize<'de, T: VariantNames, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        T::from_name(&name)
+            .ok_or_else(|| <D::Error as de::Error>::custom(format!("unknown variant: {name}")))
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<T: VariantNames, S: Serializer>(
+            value: &Option<T>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match value {
+                Some(value) => super::serialize(value, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+        pub fn deserialize<'de, T: VariantNames, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<T>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|name| {
+                    T::from_name(&name).ok_or_else(|| {
+                        <D::Error as de::Error>::custom(format!("unknown variant: {name}"))
+                    })
+                })
+                .transpose()
+        }
+    }
+}
+
+/// serde for `Order` by field
+pub(crate) mod serde_order {
+    use serde::{Deserializer, Serializer};
+
+    use super::*;
+
+    /// Mirror of `Order`, without padding
+    #[derive(Serialize, Deserialize)]
+    struct OrderFields {
+        slot: u64,
+        price: u64,
+        base_asset_amount: u64,
+        base_asset_amount_filled: u64,
+        quote_asset_amount_filled: u64,
+        trigger_price: u64,
+        auction_start_price: i64,
+        auction_end_price: i64,
+        max_ts: i64,
+        oracle_price_offset: i32,
+        order_id: u32,
+        market_index: u16,
+        #[serde(with = "serde_variant")]
+        status: OrderStatus,
+        #[serde(with = "serde_variant")]
+        order_type: OrderType,
+        #[serde(with = "serde_variant")]
+        market_type: MarketType,
+        user_order_id: u8,
+        #[serde(with = "serde_variant")]
+        existing_position_direction: PositionDirection,
+        #[serde(with = "serde_variant")]
+        direction: PositionDirection,
+        reduce_only: bool,
+        post_only: bool,
+        immediate_or_cancel: bool,
+        #[serde(with = "serde_variant")]
+        trigger_condition: OrderTriggerCondition,
+        auction_duration: u8,
+    }
+
+    impl From<&Order> for OrderFields {
+        fn from(order: &Order) -> Self {
+            Self {
+                slot: order.slot,
+                price: order.price,
+                base_asset_amount: order.base_asset_amount,
+                base_asset_amount_filled: order.base_asset_amount_filled,
+                quote_asset_amount_filled: order.quote_asset_amount_filled,
+                trigger_price: order.trigger_price,
+                auction_start_price: order.auction_start_price,
+                auction_end_price: order.auction_end_price,
+                max_ts: order.max_ts,
+                oracle_price_offset: order.oracle_price_offset,
+                order_id: order.order_id,
+                market_index: order.market_index,
+                status: order.status,
+                order_type: order.order_type,
+                market_type: order.market_type,
+                user_order_id: order.user_order_id,
+                existing_position_direction: order.existing_position_direction,
+                direction: order.direction,
+                reduce_only: order.reduce_only,
+                post_only: order.post_only,
+                immediate_or_cancel: order.immediate_or_cancel,
+                trigger_condition: order.trigger_condition,
+                auction_duration: order.auction_duration,
+            }
+        }
+    }
+
+    impl From<OrderFields> for Order {
+        fn from(fields: OrderFields) -> Self {
+            Self {
+                slot: fields.slot,
+                price: fields.price,
+                base_asset_amount: fields.base_asset_amount,
+                base_asset_amount_filled: fields.base_asset_amount_filled,
+                quote_asset_amount_filled: fields.quote_asset_amount_filled,
+                trigger_price: fields.trigger_price,
+                auction_start_price: fields.auction_start_price,
+                auction_end_price: fields.auction_end_price,
+                max_ts: fields.max_ts,
+                oracle_price_offset: fields.oracle_price_offset,
+                order_id: fields.order_id,
+                market_index: fields.market_index,
+                status: fields.status,
+                order_type: fields.order_type,
+                market_type: fields.market_type,
+                user_order_id: fields.user_order_id,
+                existing_position_direction: fields.existing_position_direction,
+                direction: fields.direction,
+                reduce_only: fields.reduce_only,
+                post_only: fields.post_only,
+                immediate_or_cancel: fields.immediate_or_cancel,
+                trigger_condition: fields.trigger_condition,
+                auction_duration: fields.auction_duration,
+                ..Default::default()
+            }
+        }
+    }
+
+    pub fn serialize<S: Serializer>(value: &Order, serializer: S) -> Result<S::Ok, S::Error> {
+        OrderFields::from(value).serialize(serializer)
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Order, D::Error> {
+        OrderFields::deserialize(deserializer).map(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::AnchorDeserialize;
+
+    use super::*;
+
+    /// Number of variants of `T` by borsh discriminant
+    fn variant_count<T: AnchorDeserialize>() -> usize {
+        (0..=u8::MAX)
+            .take_while(|discriminant| T::try_from_slice(&[*discriminant]).is_ok())
+            .count()
+    }
+
+    #[test]
+    fn variant_names_complete() {
+        fn check<T: VariantNames + AnchorDeserialize>() {
+            assert_eq!(T::VARIANTS.len(), variant_count::<T>());
+            for (discriminant, (variant, name)) in T::VARIANTS.iter().enumerate() {
+                // listed in discriminant order
+                assert!(T::try_from_slice(&[discriminant as u8]).unwrap() == *variant);
+                assert!(T::from_name(name) == Some(*variant));
+            }
+        }
+        check::<PositionDirection>();
+        check::<MarketType>();
+        check::<OrderStatus>();
+        check::<OrderType>();
+        check::<OrderTriggerCondition>();
+        check::<DepositDirection>();
+        check::<DepositExplanation>();
+        check::<LiquidationType>();
+        check::<SettlePnlExplanation>();
+        check::<LPAction>();
+        check::<StakeAction>();
+    }
+
+    #[test]
+    fn order_by_field() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Wrapper(#[serde(with = "serde_order")] Order);
+
+        let order = Order {
+            order_id: 7,
+            price: 1_000,
+            order_type: OrderType::Limit,
+            direction: PositionDirection::Short,
+            trigger_condition: OrderTriggerCondition::TriggeredBelow,
+            post_only: true,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(Wrapper(order)).unwrap();
+        assert_eq!(json["order_id"], 7);
+        assert_eq!(json["direction"], "Short");
+        assert_eq!(json["trigger_condition"], "TriggeredBelow");
+        assert_eq!(
+            serde_json::from_value::<Wrapper>(json).unwrap(),
+            Wrapper(order)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Tx signers for `Wallet`
+//!
+//! Allows custody setups where the signing key is not held by the SDK process e.g. a remote signing service
+
+use futures_util::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::{Signer, SignerError},
+};
+
+use crate::types::{SdkError, SdkResult};
+
+/// Provides tx signing for a `Wallet`
+pub trait DriftSigner: Send + Sync + 'static {
+    /// Return the signing address
+    fn address(&self) -> Pubkey;
+    /// Sign the serialized tx `message`
+    fn sign_message_bytes<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, SdkResult<Signature>>;
+}
+
+/// Sign with a local keypair
+impl DriftSigner for Keypair {
+    fn address(&self) -> Pubkey {
+        self.pubkey()
+    }
+    fn sign_message_bytes<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, SdkResult<Signature>> {
+        let signature = self.try_sign_message(message).map_err(Into::into);
+        async move { signature }.boxed()
+    }
+}
+
+/// Signer for read-only wallets, signing always fails
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ReadOnlySigner;
+
+impl DriftSigner for ReadOnlySigner {
+    fn address(&self) -> Pubkey {
+        Pubkey::default()
+    }
+    fn sign_message_bytes<'a>(&'a self, _message: &'a [u8]) -> BoxFuture<'a, SdkResult<Signature>> {
+        async move {
+            Err(SdkError::Signing(SignerError::Custom(
+                "read-only wallet".to_string(),
+            )))
+        }
+        .boxed()
+    }
+}
+
+/// Sign via a remote HTTP signing service
+///
+/// The service receives a POST request with JSON body:
+/// `{ "pubkey": "<base58 signer address>", "message": "<base64 tx message>" }`
+///
+/// and should respond with JSON: `{ "signature": "<base58 signature>" }`
+#[derive(Clone, Debug)]
+pub struct RemoteSigner {
+    url: String,
+    pubkey: Pubkey,
+    client: Client,
+}
+
+impl RemoteSigner {
+    /// Create a new remote signer
+    ///
+    /// `url` endpoint of the signing service
+    /// `pubkey` the address the service signs for
+    pub fn new(url: &str, pubkey: Pubkey) -> Self {
+        Self {
+            url: url.to_string(),
+            pubkey,
+            client: Client::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignRequest {
+    pubkey: String,
+    message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignResponse {
+    signature: String,
+}
+
+impl DriftSigner for RemoteSigner {
+    fn address(&self) -> Pubkey {
+        self.pubkey
+    }
+    fn sign_message_bytes<'a>(&'a self, message: &'a [u8]) -> BoxFuture<'a, SdkResult<Signature>> {
+        async move {
+            let request = SignRequest {
+                pubkey: self.pubkey.to_string(),
+                message: base64::encode(message),
+            };
+            let response = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(&request).expect("serializes"))
+                .send()
+                .await?
+                .error_for_status()?;
+            let body = response.bytes().await?;
+            let response: SignResponse =
+                serde_json::from_slice(body.as_ref()).map_err(|_| SdkError::Deserializing)?;
+            let signature: Signature = response
+                .signature
+                .parse()
+                .map_err(|_| SdkError::InvalidBase58)?;
+
+            if !signature.verify(self.pubkey.as_ref(), message) {
+                return Err(SdkError::Signing(SignerError::Custom(
+                    "remote signer returned invalid signature".to_string(),
+                )));
+            }
+
+            Ok(signature)
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{
+        hash::Hash,
+        message::{Message, VersionedMessage},
+        system_instruction,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::Wallet;
+
+    /// Serve a single signing request with `keypair`
+    async fn mock_signer_server(keypair: Keypair) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0_u8; 1024];
+            let body = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+                    let content_length: usize = headers
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length: ")
+                                .map(|x| x.parse().unwrap())
+                        })
+                        .unwrap_or_default();
+                    if body.len() >= content_length {
+                        break body.to_string();
+                    }
+                }
+            };
+
+            let request: SignRequest = serde_json::from_str(&body).unwrap();
+            assert_eq!(request.pubkey, keypair.pubkey().to_string());
+            let message = base64::decode(request.message).unwrap();
+            let response = serde_json::to_string(&SignResponse {
+                signature: keypair.sign_message(&message).to_string(),
+            })
+            .unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{response}",
+                response.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        url
+    }
+
+    fn test_message(payer: &Pubkey) -> VersionedMessage {
+        VersionedMessage::Legacy(Message::new(
+            &[system_instruction::transfer(
+                payer,
+                &Pubkey::new_unique(),
+                1,
+            )],
+            Some(payer),
+        ))
+    }
+
+    #[tokio::test]
+    async fn remote_signer() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let url = mock_signer_server(keypair).await;
+        let wallet = Wallet::from_signer(RemoteSigner::new(&url, pubkey));
+
+        let tx = wallet
+            .sign_tx(test_message(&pubkey), Hash::new_unique())
+            .await
+            .unwrap();
+        assert!(tx.verify_with_results().iter().all(|ok| *ok));
+    }
+
+    #[tokio::test]
+    async fn local_and_read_only_signer() {
+        let wallet = Wallet::new(Keypair::new());
+        let tx = wallet
+            .sign_tx(test_message(wallet.authority()), Hash::new_unique())
+            .await
+            .unwrap();
+        assert!(tx.verify_with_results().iter().all(|ok| *ok));
+
+        let wallet = Wallet::read_only(Pubkey::new_unique());
+        assert!(wallet
+            .sign_tx(test_message(wallet.authority()), Hash::new_unique())
+            .await
+            .is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     cell::{BorrowError, BorrowMutError},
     cmp::Ordering,
+    time::Duration,
 };
 
 use anchor_lang::AccountDeserialize;
@@ -11,26 +12,37 @@ pub use drift::{
     state::{
         order_params::{ModifyOrderParams, OrderParams, PostOnlyParam},
         perp_market::PerpMarket,
+        settle_pnl_mode::SettlePnlMode,
         spot_market::SpotMarket,
         user::{MarketType, Order, OrderType, PerpPosition, SpotPosition, User, UserStats},
     },
 };
 use futures_util::sink::Sink;
 pub use solana_client::rpc_config::RpcSendTransactionConfig;
-pub use solana_sdk::{
-    commitment_config::CommitmentConfig, message::VersionedMessage,
-    transaction::VersionedTransaction,
+use solana_client::{
+    client_error::ClientErrorKind,
+    rpc_request::{RpcError, RpcResponseErrorData},
+    rpc_response::RpcSimulateTransactionResult,
 };
 use solana_sdk::{
+    clock::Slot,
     instruction::{AccountMeta, InstructionError},
     pubkey::Pubkey,
     transaction::TransactionError,
 };
+pub use solana_sdk::{
+    commitment_config::CommitmentConfig, message::VersionedMessage,
+    transaction::VersionedTransaction,
+};
 use thiserror::Error;
 use tokio::net::TcpStream;
 use tokio_tungstenite::{tungstenite, MaybeTlsStream, WebSocketStream};
 
-use crate::Wallet;
+use crate::{
+    constants,
+    event_subscriber::{try_parse_log, DriftEvent, EventDecodeError},
+    Wallet,
+};
 
 pub type SdkResult<T> = Result<T, SdkError>;
 
@@ -224,6 +236,8 @@ pub enum SdkError {
     InvalidBase58,
     #[error("user does not have position: {0}")]
     NoPosiiton(u16),
+    #[error("sub-account does not exist: {0}")]
+    NoSubAccount(u16),
     #[error("insufficient SOL balance for fees")]
     OutOfSOL,
     #[error("{0}")]
@@ -256,22 +270,42 @@ pub enum SdkError {
     MaxReconnectionAttemptsReached,
     #[error("jit taker order not found")]
     JitOrderNotFound,
+    /// tx simulation failed with the given error and drift error code, if any
+    #[error("tx simulation failed: {0}")]
+    SimulationFailed(TransactionError, Option<ErrorCode>),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Program(#[from] solana_sdk::program_error::ProgramError),
+    #[error("{0}")]
+    EventDecode(#[from] EventDecodeError),
+    /// recorded event at the given line (or row id) could not be parsed
+    #[error("invalid event record {0}: {1}")]
+    EventRecord(usize, serde_json::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("{0}")]
+    Sqlite(#[from] rusqlite::Error),
 }
 
 impl SdkError {
     /// extract anchor error code from the SdkError if it exists
     pub fn to_anchor_error_code(&self) -> Option<ErrorCode> {
-        if let SdkError::Rpc(inner) = self {
-            if let Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) =
-                inner.get_transaction_error()
-            {
-                // inverse of anchor's 'From<ErrorCode> for u32'
-                return Some(unsafe {
-                    std::mem::transmute(code - anchor_lang::error::ERROR_CODE_OFFSET)
-                });
+        match self {
+            SdkError::Rpc(inner) => {
+                let err = inner.get_transaction_error()?;
+                // preflight logs identify the failing program, if available
+                let program = match inner.kind() {
+                    ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                        data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+                        ..
+                    }) => result.logs.as_deref().and_then(failed_program_from_logs),
+                    _ => None,
+                };
+                anchor_error_code(&err, program.as_ref())
             }
+            SdkError::SimulationFailed(_, error_code) => *error_code,
+            _ => None,
         }
-        None
     }
     /// convert to 'out of sol' error is possible
     pub fn to_out_of_sol_error(&self) -> Option<SdkError> {
@@ -286,6 +320,121 @@ impl SdkError {
         }
         None
     }
+    /// true if the error is due to a missing account
+    pub fn is_account_not_found(&self) -> bool {
+        if let SdkError::Rpc(inner) = self {
+            if let ClientErrorKind::RpcError(RpcError::ForUser(msg)) = inner.kind() {
+                return msg.starts_with("AccountNotFound");
+            }
+        }
+        false
+    }
+}
+
+/// Last variant of drift's `ErrorCode`, codes beyond it are not decoded
+///
+/// Must not be newer than the drift program version this crate is built against
+const LAST_ERROR_CODE: ErrorCode = ErrorCode::CantReclaimRent;
+
+/// extract the drift program error code from a tx error if it exists
+///
+/// `program` is the program of the failing instruction, if known. Custom errors raised by other
+/// programs are not decoded
+pub(crate) fn anchor_error_code(
+    err: &TransactionError,
+    program: Option<&Pubkey>,
+) -> Option<ErrorCode> {
+    if program.is_some_and(|p| *p != constants::PROGRAM_ID) {
+        return None;
+    }
+    if let TransactionError::InstructionError(_, InstructionError::Custom(code)) = err {
+        let code = code.checked_sub(anchor_lang::error::ERROR_CODE_OFFSET)?;
+        if code <= LAST_ERROR_CODE as u32 {
+            // inverse of anchor's 'From<ErrorCode> for u32'
+            // SAFETY: `ErrorCode` is `repr(u32)` with contiguous discriminants from 0 to `LAST_ERROR_CODE`
+            return Some(unsafe { std::mem::transmute::<u32, ErrorCode>(code) });
+        }
+    }
+    None
+}
+
+/// Find the program of the instruction that failed with `err` in `message`
+fn failed_program<'a>(message: &'a VersionedMessage, err: &TransactionError) -> Option<&'a Pubkey> {
+    if let TransactionError::InstructionError(idx, _) = err {
+        let ix = message.instructions().get(*idx as usize)?;
+        return message
+            .static_account_keys()
+            .get(ix.program_id_index as usize);
+    }
+    None
+}
+
+/// Find the failing program from tx logs i.e. 'Program <id> failed: <reason>'
+fn failed_program_from_logs(logs: &[String]) -> Option<Pubkey> {
+    logs.iter().rev().find_map(|log| {
+        let (program, _) = log.strip_prefix("Program ")?.split_once(" failed: ")?;
+        program.parse().ok()
+    })
+}
+
+/// Result of a tx simulation
+#[derive(Debug)]
+pub struct SimulationResult {
+    /// compute units consumed by the tx
+    pub units_consumed: u64,
+    /// drift events emitted by the tx
+    pub events: Vec<DriftEvent>,
+    /// raw program logs
+    pub logs: Vec<String>,
+    /// tx error, if the tx would fail
+    pub err: Option<TransactionError>,
+    /// drift error code of the failing instruction, if any
+    pub error_code: Option<ErrorCode>,
+}
+
+impl SimulationResult {
+    /// Build from the RPC simulation response of `tx`
+    pub fn new(response: RpcSimulateTransactionResult, tx: &VersionedTransaction) -> Self {
+        let signature = tx
+            .signatures
+            .first()
+            .copied()
+            .unwrap_or_default()
+            .to_string();
+        let logs = response.logs.unwrap_or_default();
+        let events = logs
+            .iter()
+            .enumerate()
+            .filter_map(|(tx_idx, log)| {
+                try_parse_log(log.as_str(), &signature, tx_idx)
+                    .ok()
+                    .flatten()
+            })
+            .collect();
+        let error_code = response
+            .err
+            .as_ref()
+            .and_then(|err| anchor_error_code(err, Some(failed_program(&tx.message, err)?)));
+
+        Self {
+            units_consumed: response.units_consumed.unwrap_or_default(),
+            events,
+            logs,
+            err: response.err,
+            error_code,
+        }
+    }
+    /// True if the tx would succeed
+    pub fn is_ok(&self) -> bool {
+        self.err.is_none()
+    }
+    /// Index of the failing instruction, if any
+    pub fn failed_ix(&self) -> Option<u8> {
+        match self.err {
+            Some(TransactionError::InstructionError(idx, _)) => Some(idx),
+            _ => None,
+        }
+    }
 }
 
 /// Helper type for Accounts included in drift instructions
@@ -362,6 +511,8 @@ pub trait MarketPrecision {
     fn quantity_tick(&self) -> u64;
     /// smallest order size
     fn min_order_size(&self) -> u64;
+    /// precision of base asset amounts i.e. amount of 1 base unit
+    fn base_precision(&self) -> u64;
 }
 
 impl MarketPrecision for SpotMarket {
@@ -374,6 +525,9 @@ impl MarketPrecision for SpotMarket {
     fn quantity_tick(&self) -> u64 {
         self.order_step_size
     }
+    fn base_precision(&self) -> u64 {
+        10_u64.pow(self.decimals)
+    }
 }
 
 impl MarketPrecision for PerpMarket {
@@ -386,6 +540,9 @@ impl MarketPrecision for PerpMarket {
     fn quantity_tick(&self) -> u64 {
         self.amm.order_step_size
     }
+    fn base_precision(&self) -> u64 {
+        drift::math::constants::BASE_PRECISION_U64
+    }
 }
 
 #[derive(Clone)]
@@ -421,6 +578,88 @@ impl ClientOpts {
     }
 }
 
+/// Parameters for estimating a tx compute budget from simulation and recent fees
+///
+/// see `TransactionBuilder::build_with_auto_compute_budget`
+#[derive(Copy, Clone, Debug)]
+pub struct AutoComputeBudget {
+    /// CUs added on top of the simulated consumption, in percent e.g. 10 => +10%
+    pub cu_margin_percent: u32,
+    /// percentile of recent priority fees to pay (0-100)
+    pub fee_percentile: u8,
+    /// # of recent slots to consider for fees
+    pub fee_window: Option<usize>,
+    /// upper bound on the priority fee in µ-lamports per CU
+    pub max_microlamports_per_cu: u64,
+}
+
+impl Default for AutoComputeBudget {
+    fn default() -> Self {
+        Self {
+            cu_margin_percent: 10,
+            fee_percentile: 75,
+            fee_window: None,
+            max_microlamports_per_cu: 1_000_000,
+        }
+    }
+}
+
+impl AutoComputeBudget {
+    /// Return the CU limit to set given simulated `units_consumed`
+    pub fn cu_limit(&self, units_consumed: u64) -> u32 {
+        let limit = units_consumed.saturating_mul(100 + self.cu_margin_percent as u64) / 100;
+        limit.min(constants::MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+    }
+    /// Return the priority fee to pay given `recent_fees` (µ-lamports per CU)
+    pub fn priority_fee(&self, recent_fees: &[u64]) -> u64 {
+        if recent_fees.is_empty() {
+            return 0;
+        }
+        let mut fees = recent_fees.to_vec();
+        fees.sort_unstable();
+        let idx = (fees.len() - 1) * (self.fee_percentile.min(100) as usize) / 100;
+        fees[idx].min(self.max_microlamports_per_cu)
+    }
+}
+
+/// Options for `DriftClient::sign_and_confirm`
+#[derive(Copy, Clone, Debug)]
+pub struct SendConfig {
+    /// commitment level at which the tx is considered landed
+    pub commitment: CommitmentConfig,
+    /// interval between rebroadcasts (and status checks) of the tx
+    pub rebroadcast_interval: Duration,
+    /// give up on the tx after this duration, even if its blockhash is still valid
+    pub timeout: Duration,
+    /// max. number of times to re-sign the tx with a fresh blockhash once its blockhash expires
+    pub max_resigns: u8,
+}
+
+impl Default for SendConfig {
+    fn default() -> Self {
+        Self {
+            commitment: CommitmentConfig::confirmed(),
+            rebroadcast_interval: Duration::from_secs(2),
+            timeout: Duration::from_secs(60),
+            max_resigns: 0,
+        }
+    }
+}
+
+/// Final outcome of a sent tx
+#[derive(Clone, Debug, PartialEq)]
+pub enum TxOutcome {
+    /// The tx landed at `slot`, `err` is set if it failed on-chain
+    Landed {
+        slot: Slot,
+        err: Option<TransactionError>,
+    },
+    /// The tx blockhash expired before the tx landed
+    Expired,
+    /// The tx was not seen landing before the send timeout
+    Dropped,
+}
+
 #[derive(Copy, Clone)]
 pub struct ReferrerInfo {
     referrer: Pubkey,
@@ -469,10 +708,27 @@ mod tests {
         rpc_response::RpcSimulateTransactionResult,
     };
     use solana_sdk::{
-        instruction::InstructionError, pubkey::Pubkey, transaction::TransactionError,
+        instruction::{Instruction, InstructionError},
+        message::{Message, VersionedMessage},
+        pubkey::Pubkey,
+        signature::Signature,
+        transaction::{TransactionError, VersionedTransaction},
     };
 
-    use super::{RemainingAccount, SdkError};
+    use super::{AutoComputeBudget, RemainingAccount, SdkError, SimulationResult};
+    use crate::constants::PROGRAM_ID;
+
+    /// tx calling `programs` in order
+    fn test_tx(programs: &[Pubkey]) -> VersionedTransaction {
+        let ixs: Vec<Instruction> = programs
+            .iter()
+            .map(|p| Instruction::new_with_bytes(*p, &[], vec![]))
+            .collect();
+        VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::Legacy(Message::new(&ixs, Some(&Pubkey::new_unique()))),
+        }
+    }
 
     #[test]
     fn extract_anchor_error() {
@@ -503,6 +759,137 @@ mod tests {
         );
     }
 
+    #[test]
+    fn account_not_found_error() {
+        let not_found = SdkError::Rpc(ClientError::from(ClientErrorKind::RpcError(
+            RpcError::ForUser(format!("AccountNotFound: pubkey={}", Pubkey::new_unique())),
+        )));
+        assert!(not_found.is_account_not_found());
+
+        let other = SdkError::Rpc(ClientError::from(ClientErrorKind::Custom(
+            "connection reset".to_string(),
+        )));
+        assert!(!other.is_account_not_found());
+        assert!(!SdkError::InvalidAccount.is_account_not_found());
+    }
+
+    #[test]
+    fn simulation_result_error_code() {
+        let other_program = Pubkey::new_unique();
+        let tx = test_tx(&[other_program, other_program, PROGRAM_ID]);
+        let response = |err| RpcSimulateTransactionResult {
+            err: Some(err),
+            logs: Some(vec![
+                "Program log: Instruction: PlaceOrders".to_string(),
+                "Program log: could not find user order id 5".to_string(),
+            ]),
+            accounts: None,
+            units_consumed: Some(21_000),
+            return_data: None,
+        };
+
+        let result = SimulationResult::new(
+            response(TransactionError::InstructionError(
+                2,
+                InstructionError::Custom(6071),
+            )),
+            &tx,
+        );
+        assert!(!result.is_ok());
+        assert_eq!(result.units_consumed, 21_000);
+        assert_eq!(result.failed_ix(), Some(2));
+        assert_eq!(result.error_code, Some(ErrorCode::UserOrderIdAlreadyInUse));
+        assert_eq!(result.events.len(), 1);
+
+        // non-drift program error in drift's code range
+        let result = SimulationResult::new(
+            response(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(6071),
+            )),
+            &tx,
+        );
+        assert_eq!(result.failed_ix(), Some(0));
+        assert!(result.error_code.is_none());
+
+        // drift program, unknown error code
+        let result = SimulationResult::new(
+            response(TransactionError::InstructionError(
+                2,
+                InstructionError::Custom(u32::MAX),
+            )),
+            &tx,
+        );
+        assert!(result.error_code.is_none());
+        let result = SimulationResult::new(
+            response(TransactionError::InstructionError(
+                2,
+                InstructionError::Custom(1),
+            )),
+            &tx,
+        );
+        assert!(result.error_code.is_none());
+    }
+
+    #[test]
+    fn preflight_error_from_other_program() {
+        let preflight_failure = |logs| {
+            SdkError::Rpc(ClientError::from(ClientErrorKind::RpcError(
+                RpcError::RpcResponseError {
+                    code: -32002,
+                    message: "Transaction simulation failed".to_string(),
+                    data: solana_client::rpc_request::RpcResponseErrorData::SendTransactionPreflightFailure(
+                        RpcSimulateTransactionResult {
+                            err: Some(TransactionError::InstructionError(0, InstructionError::Custom(6071))),
+                            logs: Some(logs),
+                            accounts: None,
+                            units_consumed: None,
+                            return_data: None,
+                        },
+                    ),
+                },
+            )))
+        };
+
+        let err = preflight_failure(vec![format!(
+            "Program {} failed: custom program error: 0x17b7",
+            Pubkey::new_unique()
+        )]);
+        assert!(err.to_anchor_error_code().is_none());
+
+        let err = preflight_failure(vec![format!(
+            "Program {PROGRAM_ID} failed: custom program error: 0x17b7"
+        )]);
+        assert_eq!(
+            err.to_anchor_error_code(),
+            Some(ErrorCode::UserOrderIdAlreadyInUse)
+        );
+    }
+
+    #[test]
+    fn auto_compute_budget() {
+        let config = AutoComputeBudget {
+            cu_margin_percent: 20,
+            fee_percentile: 50,
+            fee_window: None,
+            max_microlamports_per_cu: 5_000,
+        };
+        assert_eq!(config.cu_limit(100_000), 120_000);
+        assert_eq!(
+            config.cu_limit(1_300_000),
+            crate::constants::MAX_COMPUTE_UNIT_LIMIT
+        );
+
+        assert_eq!(config.priority_fee(&[]), 0);
+        assert_eq!(config.priority_fee(&[500, 100, 300, 200, 400]), 300);
+        assert_eq!(config.priority_fee(&[10_000, 20_000]), 5_000);
+        let config = AutoComputeBudget {
+            fee_percentile: 100,
+            ..config
+        };
+        assert_eq!(config.priority_fee(&[500, 100, 300, 200, 400]), 500);
+    }
+
     #[test]
     fn account_type_sorting() {
         let mut accounts = vec![
@@ -1,8 +1,9 @@
+use std::any::Any;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
-use crate::event_emitter::EventEmitter;
+use crate::event_emitter::{Event, EventEmitter};
 use crate::memcmp::{get_non_idle_user_filter, get_user_filter};
 use crate::utils::{decode, get_ws_url};
 use crate::websocket_program_account_subscriber::{
@@ -22,6 +23,29 @@ use solana_client::rpc_response::{OptionalContext, RpcKeyedAccount};
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
 
+/// A change to a user account observed by `UserMap`
+#[derive(Clone, Debug)]
+pub struct UserUpdate {
+    /// the user account address
+    pub pubkey: Pubkey,
+    /// the previous account state, if it was known
+    pub old: Option<User>,
+    /// the latest account state
+    pub new: User,
+    /// slot of the update
+    pub slot: u64,
+}
+
+impl Event for UserUpdate {
+    fn box_clone(&self) -> Box<dyn Event> {
+        Box::new((*self).clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
 pub struct UserMap {
     subscribed: bool,
     subscription: WebsocketProgramAccountSubscriber,
@@ -29,11 +53,14 @@ pub struct UserMap {
     sync_lock: Option<Mutex<()>>,
     latest_slot: Arc<AtomicU64>,
     commitment: CommitmentConfig,
-    rpc: RpcClient,
+    rpc: Arc<RpcClient>,
+    /// emits a `UserUpdate` for every user account change received via websocket
+    pub event_emitter: EventEmitter,
 }
 
 impl UserMap {
     pub const SUBSCRIPTION_ID: &'static str = "usermap";
+    pub const USER_UPDATE_ID: &'static str = "user_update";
 
     pub fn new(
         commitment: CommitmentConfig,
@@ -61,7 +88,7 @@ impl UserMap {
 
         let usermap = Arc::new(DashMap::new());
 
-        let rpc = RpcClient::new_with_commitment(endpoint.clone(), commitment);
+        let rpc = Arc::new(RpcClient::new_with_commitment(endpoint.clone(), commitment));
 
         let sync_lock = if sync { Some(Mutex::new(())) } else { None };
 
@@ -73,6 +100,7 @@ impl UserMap {
             latest_slot: Arc::new(AtomicU64::new(0)),
             commitment,
             rpc,
+            event_emitter: EventEmitter::new(),
         }
     }
 
@@ -87,6 +115,7 @@ impl UserMap {
 
             let usermap = self.usermap.clone();
             let latest_slot = self.latest_slot.clone();
+            let event_emitter = self.event_emitter.clone();
 
             self.subscription
                 .event_emitter
@@ -99,7 +128,18 @@ impl UserMap {
                         if update.data_and_slot.slot > latest_slot.load(Ordering::Relaxed) {
                             latest_slot.store(update.data_and_slot.slot, Ordering::Relaxed);
                         }
-                        usermap.insert(user_pubkey, user_data_and_slot.data);
+                        let old = usermap.insert(user_pubkey, user_data_and_slot.data);
+                        if let Ok(pubkey) = Pubkey::from_str(&update.pubkey) {
+                            event_emitter.emit(
+                                UserMap::USER_UPDATE_ID,
+                                Box::new(UserUpdate {
+                                    pubkey,
+                                    old,
+                                    new: user_data_and_slot.data,
+                                    slot: user_data_and_slot.slot,
+                                }),
+                            );
+                        }
                     }
                 });
         }
@@ -191,6 +231,10 @@ impl UserMap {
     pub fn get_latest_slot(&self) -> u64 {
         self.latest_slot.load(Ordering::Relaxed)
     }
+
+    pub(crate) fn rpc(&self) -> &Arc<RpcClient> {
+        &self.rpc
+    }
 }
 
 #[cfg(test)]
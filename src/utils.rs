@@ -6,7 +6,7 @@ use serde_json::json;
 use solana_account_decoder::UiAccountData;
 use solana_sdk::{
     account::Account, address_lookup_table_account::AddressLookupTableAccount, bs58,
-    pubkey::Pubkey, signature::Keypair,
+    message::VersionedMessage, pubkey::Pubkey, signature::Keypair, system_program,
 };
 
 use crate::types::{SdkError, SdkResult};
@@ -130,6 +130,29 @@ where
     T::try_deserialize(&mut decoded_data_slice).map_err(|err| SdkError::Anchor(Box::new(err)))
 }
 
+/// Encode a drift account name e.g. sub-account names
+///
+/// Names are stored on-chain as 32 bytes padded with spaces, longer names are truncated
+pub fn encode_name(name: &str) -> [u8; 32] {
+    let mut encoded = [b' '; 32];
+    let len = name.len().min(32);
+    encoded[..len].copy_from_slice(&name.as_bytes()[..len]);
+    encoded
+}
+
+/// Returns true if the tx `message` uses a durable nonce i.e. its first ix advances a nonce account
+pub fn uses_durable_nonce(message: &VersionedMessage) -> bool {
+    // bincode encoded `SystemInstruction::AdvanceNonceAccount`
+    const ADVANCE_NONCE_ACCOUNT: [u8; 4] = 4_u32.to_le_bytes();
+    message.instructions().first().is_some_and(|ix| {
+        message
+            .static_account_keys()
+            .get(ix.program_id_index as usize)
+            == Some(&system_program::ID)
+            && ix.data == ADVANCE_NONCE_ACCOUNT
+    })
+}
+
 pub(crate) fn market_type_to_string(market_type: &MarketType) -> String {
     match market_type {
         MarketType::Perp => "perp".to_string(),
@@ -205,6 +228,16 @@ mod tests {
         assert!(keypair.pubkey().to_string() == "EtiM5qwcrrawQP9FfRErBatNvDgEU656tk5aA8iTgqri");
     }
 
+    #[test]
+    fn test_encode_name() {
+        let name = encode_name("Main Account");
+        assert_eq!(&name[..12], b"Main Account");
+        assert!(name[12..].iter().all(|b| *b == b' '));
+
+        let long_name = "a".repeat(40);
+        assert_eq!(encode_name(&long_name), [b'a'; 32]);
+    }
+
     #[test]
     fn test_https_to_ws() {
         let https_url = "https://dlob.drift.trade";